@@ -2,7 +2,8 @@ use baked_fluent::{impl_localize, localize, Localize};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 impl_localize! {
-    #[localize(path = "tests/i18n", default_locale = "en_US")]
+    #[path("tests/i18n")]
+    #[default_locale("en_US")]
     struct TestLocalizer(_);
 }
 
@@ -25,19 +26,14 @@ fn bench(c: &mut Criterion) {
         let loc = TestLocalizer::new(&["en_US"], None);
 
         b.iter(|| {
-            black_box(localize!(loc, title));
+            black_box(localize!(loc, title).unwrap());
         });
     });
     c.bench_function("localize-moderate", |b| {
         let loc = TestLocalizer::new(&["en_US"], None);
 
         b.iter(|| {
-            black_box(localize!(
-                loc,
-                greeting,
-                name = ("Jamie"),
-                friends = black_box(12)
-            ));
+            black_box(localize!(loc, greeting, name = ("Jamie"), friends = black_box(12)).unwrap());
         });
     });
 }
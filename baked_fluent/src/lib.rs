@@ -48,8 +48,16 @@
 pub mod integrations;
 pub mod runtime;
 
+pub use runtime::{Catalog, CatalogError, CatalogLocalizer};
+
 /// `Localize` trait; can be included in templates to allow using the `localize` filter.
 /// Implementations are generally derived.
+///
+/// Messages are formatted the way `fluent-bundle` formats them, except that they're written out
+/// as they're resolved. So a reference that would close a cycle only replaces its own placeable
+/// with `___`, and the rest of the message is still written (`"x ___ y"`), where `fluent-bundle`
+/// would replace the whole message. `impl_localize!` rejects cycles in a localizer's own
+/// translations, so this can only happen through `#[include_sources(...)]`.
 pub trait Localize: Sized {
     // Implementation notes:
     // All of the code that actually talks to fluent is in the `baked_fluent::runtime` module.
@@ -64,12 +72,18 @@ pub trait Localize: Sized {
     /// - `accept_language`: an `Accept-Language` HTTP header, if present.
     fn new(user_locales: &[&str], accept_language: Option<&str>) -> Self;
 
+    /// Look up the ID of a message, or of an attribute (`"message.attribute"`).
+    /// IDs are also available as associated constants of generated localizers; see `MessageId`.
+    ///
+    /// Hand-written localizers that only implement `localize_into` and `has_message` don't have
+    /// IDs, and by default find none.
+    fn message_id(message_id: &str) -> Option<MessageId> {
+        let _ = message_id;
+        None
+    }
+
     /// Localize a particular message.
-    fn localize(
-        &self,
-        message_id: &'static str,
-        args: &[(&str, &runtime::I18nValue)],
-    ) -> Result<String> {
+    fn localize(&self, message_id: &str, args: &[(&str, &runtime::I18nValue)]) -> Result<String> {
         let mut result = String::new();
         self.localize_into(&mut result, message_id, args)?;
         Ok(result)
@@ -79,18 +93,89 @@ pub trait Localize: Sized {
     fn localize_into<W: std::fmt::Write>(
         &self,
         writer: &mut W,
-        message_id: &'static str,
+        message_id: &str,
+        args: &[(&str, &runtime::I18nValue)],
+    ) -> Result<()> {
+        match Self::message_id(message_id) {
+            Some(id) => self.localize_id_into(writer, id, args),
+            None => Err(Error::NoTranslations {
+                message: message_id.to_string(),
+                locale_chain: self.locale_chain().iter().map(|l| l.to_string()).collect(),
+            }),
+        }
+    }
+
+    /// Localize a particular message into a std::io::Write, e.g. a socket or file.
+    /// Output is written as UTF-8.
+    fn localize_into_io<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        message_id: &str,
+        args: &[(&str, &runtime::I18nValue)],
+    ) -> Result<()> {
+        let mut writer = runtime::IoWriter::new(writer);
+        self.localize_into(&mut writer, message_id, args)
+            .map_err(|err| match writer.take_error() {
+                Some(io) => Error::Io(std::sync::Arc::new(io)),
+                None => err,
+            })
+    }
+
+    /// Localize a particular message, by ID. Skips looking up the message's name.
+    fn localize_id(
+        &self,
+        message: MessageId,
+        args: &[(&str, &runtime::I18nValue)],
+    ) -> Result<String> {
+        let mut result = String::new();
+        self.localize_id_into(&mut result, message, args)?;
+        Ok(result)
+    }
+
+    /// Localize a particular message, by ID, into a std::fmt::Write.
+    /// By default, localizers don't have IDs, so there's no message to localize.
+    fn localize_id_into<W: std::fmt::Write>(
+        &self,
+        writer: &mut W,
+        message: MessageId,
         args: &[(&str, &runtime::I18nValue)],
-    ) -> Result<()>;
+    ) -> Result<()> {
+        let _ = (writer, args);
+        Err(Error::NoTranslations {
+            message: format!("{:?}", message),
+            locale_chain: self.locale_chain().iter().map(|l| l.to_string()).collect(),
+        })
+    }
 
-    /// Whether a localizer has a particular message available.
-    fn has_message(&self, message_id: &'static str) -> bool;
+    /// Whether a localizer has a particular message available, in any locale of its chain.
+    /// `message_id` may also name an attribute, as in `"message.attribute"`.
+    fn has_message(&self, message_id: &str) -> bool {
+        match Self::message_id(message_id) {
+            Some(id) => self.has_message_id(id),
+            None => false,
+        }
+    }
+
+    /// Whether a localizer has a particular message available, by ID.
+    /// This is a constant-time lookup. By default, localizers don't have IDs, so it's `false`.
+    fn has_message_id(&self, message: MessageId) -> bool {
+        let _ = message;
+        false
+    }
 
     /// Get the chain of locales this localizer looks up messages in.
     fn locale_chain(&self) -> &[&'static str];
 
     /// Default locale of this Localize implementation.
     fn default_locale() -> &'static str;
+
+    /// Load the translations for some locales ahead of time, e.g. the most popular ones at
+    /// startup. Only does anything for localizers generated with `#[lazy(true)]`, which otherwise
+    /// parse each locale on its first use.
+    /// - `locales`: locale names; short-form locales (e.g. "en") warm up every matching locale.
+    fn warm_up(locales: &[&str]) {
+        let _ = locales;
+    }
 }
 
 #[macro_export]
@@ -111,14 +196,79 @@ macro_rules! localize_into {
     };
 }
 
+#[macro_export]
+macro_rules! localize_into_io {
+    ($localizer:expr, $writer:expr, $message:ident $(. $attr:ident)* $(, $key:ident = $val:expr)* $(,)*) => {
+        $crate::Localize::localize_into_io(&$localizer, $writer, concat!(stringify!($message), $(".", stringify!($attr)),*), &[
+            $((stringify!($key), &$val.into())),*
+        ])
+    };
+}
+
+/// The ID of a message or attribute of a particular `Localize` implementation.
+///
+/// `impl_localize!` numbers messages densely and generates an associated constant for each one,
+/// named after the message in upper case, with `-` replaced by `_` and `.` by `__`: `greeting` is
+/// `AppLocalizer::GREETING`, and `greeting.title` is `AppLocalizer::GREETING__TITLE`.
+/// Using these with `Localize::localize_id` skips hashing the message's name on every call.
+/// Messages whose constants would have the same name, like `hello` and `Hello`, don't get one,
+/// and `impl_localize!` warns about them; `Localize::message_id` still finds their IDs.
+///
+/// IDs are only meaningful to the localizer that generated them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageId(u32);
+impl MessageId {
+    #[doc(hidden)]
+    pub const fn new(index: u32) -> MessageId {
+        MessageId(index)
+    }
+
+    /// The index of this message in its localizer's message table.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// How much of the default locale's messages and attributes a locale translates.
+///
+/// `impl_localize!` works these out at compile time, and generates an associated function,
+/// `translation_coverage()`, returning one for each baked in locale (the default locale
+/// included).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    pub locale: &'static str,
+    /// The number of messages and attributes the default locale defines.
+    pub total: usize,
+    /// The ones this locale doesn't, sorted: `"greeting"`, `"greeting.title"`.
+    pub missing: &'static [&'static str],
+}
+
+impl Coverage {
+    /// `total`, less the `missing` messages and attributes.
+    pub fn translated(&self) -> usize {
+        self.total - self.missing.len()
+    }
+
+    /// `translated` as a percentage of `total`. A localizer without any messages counts as
+    /// completely translated.
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            100.0 * self.translated() as f64 / self.total as f64
+        }
+    }
+}
+
 /// An error in localization.
 #[derive(Debug, Clone)]
 pub enum Error {
     NoTranslations {
-        message: &'static str,
-        locale_chain: Box<[&'static str]>,
+        message: String,
+        locale_chain: Box<[String]>,
     },
     Fmt(std::fmt::Error),
+    Io(std::sync::Arc<std::io::Error>),
 }
 impl From<std::fmt::Error> for Error {
     fn from(err: std::fmt::Error) -> Self {
@@ -130,11 +280,13 @@ impl std::error::Error for Error {
         match *self {
             Error::NoTranslations { .. } => "no translations",
             Error::Fmt(..) => "formatter error",
+            Error::Io(..) => "io error",
         }
     }
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
             Error::Fmt(ref e) => Some(e),
+            Error::Io(ref e) => Some(&**e),
             _ => None,
         }
     }
@@ -151,6 +303,7 @@ impl std::fmt::Display for Error {
                 message, locale_chain
             ),
             Error::Fmt(ref e) => write!(f, "fmt error: {}", e),
+            Error::Io(ref e) => write!(f, "io error: {}", e),
         }
     }
 }
@@ -176,7 +329,19 @@ mod tests {
             write!(writer, "localize {:?} {:?}", message_id, args)?;
             Ok(())
         }
-        fn has_message(&self, _: &str) -> bool {
+        fn message_id(_: &str) -> Option<MessageId> {
+            Some(MessageId::new(0))
+        }
+        fn localize_id_into<W: std::fmt::Write>(
+            &self,
+            writer: &mut W,
+            message: MessageId,
+            args: &[(&str, &runtime::I18nValue)],
+        ) -> Result<()> {
+            write!(writer, "localize {:?} {:?}", message, args)?;
+            Ok(())
+        }
+        fn has_message_id(&self, _: MessageId) -> bool {
             true
         }
         /// Get the chain of locales this localizer looks up messages in.
@@ -187,6 +352,8 @@ mod tests {
         fn default_locale() -> &'static str {
             "en_US"
         }
+
+        fn warm_up(_: &[&str]) {}
     }
     #[test]
     fn localize_macro() -> Result<()> {
@@ -211,6 +378,48 @@ mod tests {
             "localize \"bees.banana\" [(\"x\", Number(\"1\")), (\"y\", String(\"hello\")), (\"z\", String(\"there\"))]"
         );
 
+        let mut bytes = Vec::new();
+        localize_into_io!(t, &mut bytes, bees.banana, x = 1)?;
+        assert_eq!(bytes, b"localize \"bees.banana\" [(\"x\", Number(\"1\"))]");
+
+        Ok(())
+    }
+
+    /// A hand-written localizer, implementing only what `Localize` always required.
+    struct Strings;
+    impl Localize for Strings {
+        fn new(_: &[&str], _: Option<&str>) -> Self {
+            Strings
+        }
+        fn localize_into<W: std::fmt::Write>(
+            &self,
+            writer: &mut W,
+            message_id: &str,
+            _: &[(&str, &runtime::I18nValue)],
+        ) -> Result<()> {
+            write!(writer, "localize {:?}", message_id)?;
+            Ok(())
+        }
+        fn has_message(&self, message_id: &str) -> bool {
+            message_id == "hello"
+        }
+        fn locale_chain(&self) -> &[&'static str] {
+            &["en-US"]
+        }
+        fn default_locale() -> &'static str {
+            "en_US"
+        }
+    }
+
+    #[test]
+    fn string_methods_only() -> Result<()> {
+        let strings = Strings;
+        assert_eq!(localize!(strings, hello)?, "localize \"hello\"");
+        assert!(strings.has_message("hello"));
+        assert_eq!(Strings::message_id("hello"), None);
+        assert!(!strings.has_message_id(MessageId::new(0)));
+        assert!(strings.localize_id(MessageId::new(0), &[]).is_err());
+        Strings::warm_up(&["en"]);
         Ok(())
     }
 
@@ -0,0 +1,291 @@
+//! A parser for `Accept-Language` HTTP headers.
+//!
+//! Semantics follow [RFC 7231 §5.3.5](https://tools.ietf.org/html/rfc7231#section-5.3.5):
+//! - ranges are ordered by their `q=` weight, highest first; ranges with equal weights keep the
+//!   order they appeared in.
+//! - ranges with `q=0` are "not acceptable"; they're reported in `AcceptLanguage::excluded`
+//!   instead of `AcceptLanguage::ranges`.
+//! - `*` matches any language; since there's no way to know which one the user would prefer,
+//!   it's treated as "use the default locale".
+//! - entries that don't parse (bad tags, bad weights, unknown parameters) are skipped and
+//!   reported in `AcceptLanguage::invalid`.
+//!
+//! Headers come from untrusted clients, so parsing stops after `MAX_HEADER_LEN` bytes or
+//! `MAX_RANGES` entries, whichever comes first.
+
+use std::cmp::Reverse;
+
+/// The maximum number of header bytes that will be parsed.
+pub const MAX_HEADER_LEN: usize = 1024;
+
+/// The maximum number of entries that will be parsed.
+pub const MAX_RANGES: usize = 32;
+
+/// A parsed `Accept-Language` header.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AcceptLanguage<'a> {
+    /// Acceptable language ranges, in descending order of preference.
+    pub ranges: Vec<LanguageRange<'a>>,
+
+    /// Language ranges the user marked as not acceptable (`q=0`).
+    pub excluded: Vec<&'a str>,
+
+    /// Entries that couldn't be parsed and were ignored.
+    pub invalid: Vec<&'a str>,
+
+    /// Whether parsing stopped early because the header exceeded `MAX_HEADER_LEN` or `MAX_RANGES`.
+    pub truncated: bool,
+}
+
+/// A single language range in an `Accept-Language` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LanguageRange<'a> {
+    /// The range, e.g. "en", "en-US", or "*".
+    pub range: &'a str,
+
+    /// The `q=` weight, in thousandths (so `q=0.5` is 500). Defaults to 1000.
+    pub quality: u16,
+}
+
+impl<'a> LanguageRange<'a> {
+    /// Whether this is the `*` range.
+    pub fn is_wildcard(&self) -> bool {
+        self.range == "*"
+    }
+}
+
+impl<'a> AcceptLanguage<'a> {
+    /// Parse an `Accept-Language` header. Never fails; problems are recorded in the result.
+    pub fn parse(header: &'a str) -> AcceptLanguage<'a> {
+        let mut result = AcceptLanguage::default();
+
+        let header = if header.len() > MAX_HEADER_LEN {
+            result.truncated = true;
+            // cut at the last complete entry; ',' is ascii, so this is always a char boundary.
+            match header.as_bytes()[..MAX_HEADER_LEN]
+                .iter()
+                .rposition(|b| *b == b',')
+            {
+                Some(end) => &header[..end],
+                None => "",
+            }
+        } else {
+            header
+        };
+
+        let mut count = 0;
+        for entry in header.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                // the RFC allows empty list elements
+                continue;
+            }
+            if count == MAX_RANGES {
+                result.truncated = true;
+                break;
+            }
+            count += 1;
+
+            match parse_entry(entry) {
+                Some(range) if range.quality == 0 => result.excluded.push(range.range),
+                Some(range) => result.ranges.push(range),
+                None => result.invalid.push(entry),
+            }
+        }
+
+        // stable, so equal weights keep header order
+        result.ranges.sort_by_key(|range| Reverse(range.quality));
+
+        result
+    }
+
+    /// Whether `locale` was marked as not acceptable, either directly or by a less specific range
+    /// (e.g. `en;q=0` excludes "en_US").
+    pub fn excludes(&self, locale: &str) -> bool {
+        self.excluded
+            .iter()
+            .any(|range| *range != "*" && range_matches(range, locale))
+    }
+}
+
+/// Whether a language range (e.g. "en") matches a locale (e.g. "en_US").
+/// Comparison is case-insensitive, and treats '-' and '_' as the same separator.
+pub fn range_matches(range: &str, locale: &str) -> bool {
+    if range.len() > locale.len() {
+        return false;
+    }
+    let same = range
+        .bytes()
+        .zip(locale.bytes())
+        .all(|(a, b)| normalize(a) == normalize(b));
+
+    same && (range.len() == locale.len() || normalize(locale.as_bytes()[range.len()]) == b'-')
+}
+
+fn normalize(b: u8) -> u8 {
+    if b == b'_' {
+        b'-'
+    } else {
+        b.to_ascii_lowercase()
+    }
+}
+
+/// Parse an entry like `en-US;q=0.5`.
+fn parse_entry(entry: &str) -> Option<LanguageRange<'_>> {
+    let mut parts = entry.split(';');
+    let range = parts.next()?.trim();
+    if !is_valid_range(range) {
+        return None;
+    }
+
+    let mut quality = None;
+    for param in parts {
+        let param = param.trim();
+        let value = if param.starts_with("q=") || param.starts_with("Q=") {
+            &param[2..]
+        } else {
+            // Accept-Language doesn't allow any other parameters
+            return None;
+        };
+        if quality.is_some() {
+            return None;
+        }
+        quality = Some(parse_quality(value)?);
+    }
+
+    Some(LanguageRange {
+        range,
+        quality: quality.unwrap_or(1000),
+    })
+}
+
+/// Check a range against `1*8ALPHA *("-" 1*8alphanum) / "*"`.
+/// '_' is also accepted as a separator, since that's what our locale names use.
+fn is_valid_range(range: &str) -> bool {
+    if range == "*" {
+        return true;
+    }
+    range.split(&['-', '_'][..]).enumerate().all(|(i, subtag)| {
+        !subtag.is_empty()
+            && subtag.len() <= 8
+            && subtag.bytes().all(|b| {
+                if i == 0 {
+                    b.is_ascii_alphabetic()
+                } else {
+                    b.is_ascii_alphanumeric()
+                }
+            })
+    })
+}
+
+/// Parse a weight per `qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )`,
+/// in thousandths.
+fn parse_quality(value: &str) -> Option<u16> {
+    let (whole, fraction) = match value.find('.') {
+        Some(dot) => (&value[..dot], &value[dot + 1..]),
+        None => (value, ""),
+    };
+    if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut thousandths = 0;
+    for (i, digit) in fraction.bytes().enumerate() {
+        thousandths += u16::from(digit - b'0') * [100, 10, 1][i];
+    }
+    match whole {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(range: &str, quality: u16) -> LanguageRange<'_> {
+        LanguageRange { range, quality }
+    }
+
+    #[test]
+    fn parse() {
+        let parsed = AcceptLanguage::parse("de;q=0.3, en-US , en;q=0.7, *;q=0.1");
+        assert_eq!(
+            parsed.ranges,
+            &[
+                range("en-US", 1000),
+                range("en", 700),
+                range("de", 300),
+                range("*", 100)
+            ]
+        );
+        assert!(parsed.excluded.is_empty());
+        assert!(parsed.invalid.is_empty());
+        assert!(!parsed.truncated);
+
+        // equal weights keep header order
+        let parsed = AcceptLanguage::parse("es;q=0.5,fr;q=0.5,de");
+        assert_eq!(
+            parsed.ranges,
+            &[range("de", 1000), range("es", 500), range("fr", 500)]
+        );
+
+        // empty headers and list elements are fine
+        assert_eq!(AcceptLanguage::parse(""), AcceptLanguage::default());
+        assert_eq!(
+            AcceptLanguage::parse(" , en,,").ranges,
+            &[range("en", 1000)]
+        );
+    }
+
+    #[test]
+    fn exclusion() {
+        let parsed = AcceptLanguage::parse("*, en;q=0, es_MX;q=0.000");
+        assert_eq!(parsed.ranges, &[range("*", 1000)]);
+        assert_eq!(parsed.excluded, &["en", "es_MX"]);
+        assert!(parsed.excludes("en_US"));
+        assert!(parsed.excludes("EN"));
+        assert!(parsed.excludes("es-mx"));
+        assert!(!parsed.excludes("es"));
+        assert!(!parsed.excludes("eng"));
+
+        // `*;q=0` doesn't exclude anything on its own
+        assert!(!AcceptLanguage::parse("*;q=0").excludes("en_US"));
+    }
+
+    #[test]
+    fn invalid() {
+        let parsed = AcceptLanguage::parse(
+            "en;q=2, fr;q=0.5555, de;q=abc, es;level=1, x y, toolongsubtag, 1en, en--US, zh;q=0.5;q=0.4, ja",
+        );
+        assert_eq!(parsed.ranges, &[range("ja", 1000)]);
+        assert_eq!(
+            parsed.invalid,
+            &[
+                "en;q=2",
+                "fr;q=0.5555",
+                "de;q=abc",
+                "es;level=1",
+                "x y",
+                "toolongsubtag",
+                "1en",
+                "en--US",
+                "zh;q=0.5;q=0.4"
+            ]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let long = "en,".repeat(MAX_HEADER_LEN);
+        let parsed = AcceptLanguage::parse(&long);
+        assert!(parsed.truncated);
+        assert_eq!(parsed.ranges.len(), MAX_RANGES);
+
+        let long = format!("en, {}", "a".repeat(MAX_HEADER_LEN));
+        let parsed = AcceptLanguage::parse(&long);
+        assert!(parsed.truncated);
+        assert_eq!(parsed.ranges, &[range("en", 1000)]);
+        assert!(parsed.invalid.is_empty());
+    }
+}
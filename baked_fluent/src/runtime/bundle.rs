@@ -0,0 +1,187 @@
+//! The translations for a single locale.
+//!
+//! `fluent_bundle::FluentBundle` borrows its resources, so it can't be stored next to them without
+//! self-referential tricks (see https://github.com/projectfluent/fluent-rs/issues/103). A `Bundle`
+//! owns its resources instead, and refers to their entries by index.
+
+use std::collections::HashMap;
+
+use fluent_bundle::FluentResource;
+use fluent_locale::{negotiate_languages, NegotiationStrategy};
+use fluent_syntax::ast;
+use intl_pluralrules::{IntlPluralRules, PluralRuleType};
+use log::warn;
+
+/// The location of a message or term: an index into `Bundle::resources`, and an index into that
+/// resource's body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EntryRef {
+    resource: u32,
+    entry: u32,
+}
+
+/// The location of a message's value, or of one of its attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternRef {
+    message: EntryRef,
+    attribute: Option<u32>,
+}
+
+/// The translations for a single locale.
+pub struct Bundle {
+    resources: Vec<FluentResource>,
+    messages: HashMap<Box<str>, EntryRef>,
+    terms: HashMap<Box<str>, EntryRef>,
+    pub plural_rules: IntlPluralRules,
+}
+
+impl Bundle {
+    /// Create a bundle from parsed resources.
+    /// If a message or term is defined more than once, the first definition wins.
+    pub fn new(locale: &str, resources: Vec<FluentResource>) -> Bundle {
+        let mut messages = HashMap::new();
+        let mut terms = HashMap::new();
+        for (r, resource) in resources.iter().enumerate() {
+            for (e, entry) in resource.ast().body.iter().enumerate() {
+                let (entries, id) = match entry {
+                    ast::ResourceEntry::Entry(ast::Entry::Message(message)) => {
+                        (&mut messages, message.id.name)
+                    }
+                    ast::ResourceEntry::Entry(ast::Entry::Term(term)) => (&mut terms, term.id.name),
+                    _ => continue,
+                };
+                let entry = EntryRef {
+                    resource: r as u32,
+                    entry: e as u32,
+                };
+                if entries.contains_key(id) {
+                    warn!("{}: {} is defined more than once, ignoring", locale, id);
+                    continue;
+                }
+                entries.insert(Box::from(id), entry);
+            }
+        }
+
+        // same as `FluentBundle::new`
+        let plural_locale = negotiate_languages(
+            &[locale],
+            IntlPluralRules::get_locales(PluralRuleType::CARDINAL),
+            Some("en"),
+            &NegotiationStrategy::Lookup,
+        )[0]
+        .to_owned();
+        let plural_rules = IntlPluralRules::create(&plural_locale, PluralRuleType::CARDINAL)
+            .expect("failed to initialize plural rules");
+
+        Bundle {
+            resources,
+            messages,
+            terms,
+            plural_rules,
+        }
+    }
+
+    /// Look up a message.
+    pub fn message(&self, id: &str) -> Option<&ast::Message<'_>> {
+        match self.entry(*self.messages.get(id)?) {
+            ast::ResourceEntry::Entry(ast::Entry::Message(message)) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Look up a term, by its name without the leading `-`.
+    pub fn term(&self, id: &str) -> Option<&ast::Term<'_>> {
+        match self.entry(*self.terms.get(id)?) {
+            ast::ResourceEntry::Entry(ast::Entry::Term(term)) => Some(term),
+            _ => None,
+        }
+    }
+
+    /// Find a message or attribute (`message.attribute`).
+    pub fn locate(&self, path: &str) -> Option<PatternRef> {
+        let (message_id, attribute) = match path.find('.') {
+            Some(dot) => (&path[..dot], Some(&path[dot + 1..])),
+            None => (path, None),
+        };
+        let message = *self.messages.get(message_id)?;
+        let attribute = match attribute {
+            Some(attribute) => Some(
+                self.message(message_id)?
+                    .attributes
+                    .iter()
+                    .position(|attr| attr.id.name == attribute)? as u32,
+            ),
+            None => None,
+        };
+        Some(PatternRef { message, attribute })
+    }
+
+    /// The pattern of a located message or attribute; `None` if it's a message without a value.
+    pub fn pattern(&self, pattern: PatternRef) -> Option<&ast::Pattern<'_>> {
+        let message = match self.entry(pattern.message) {
+            ast::ResourceEntry::Entry(ast::Entry::Message(message)) => message,
+            _ => return None,
+        };
+        match pattern.attribute {
+            Some(attribute) => Some(&message.attributes[attribute as usize].value),
+            None => message.value.as_ref(),
+        }
+    }
+
+    /// The IDs of all messages and attributes (`message.attribute`) in this bundle.
+    pub fn paths<'a>(&'a self) -> impl Iterator<Item = String> + 'a {
+        self.messages.keys().flat_map(move |id| {
+            let attributes = self
+                .message(id)
+                .map(|message| &message.attributes[..])
+                .unwrap_or(&[]);
+            Some(id.to_string()).into_iter().chain(
+                attributes
+                    .iter()
+                    .map(move |attr| format!("{}.{}", id, attr.id.name)),
+            )
+        })
+    }
+
+    fn entry(&self, entry: EntryRef) -> &ast::ResourceEntry<'_> {
+        &self.resources[entry.resource as usize].ast().body[entry.entry as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let resources = vec![
+            FluentResource::try_new("a = A\n    .title = T\n-a = term\nb = B".to_string()).unwrap(),
+            FluentResource::try_new("b = shadowed\nc =\n    .x = X".to_string()).unwrap(),
+        ];
+        let bundle = Bundle::new("en_US", resources);
+
+        assert!(bundle.message("a").is_some());
+        assert!(bundle.term("a").is_some());
+        assert!(bundle.term("b").is_none());
+
+        let text = |path: &str| {
+            let pattern = bundle.pattern(bundle.locate(path)?)?;
+            match &pattern.elements[0] {
+                ast::PatternElement::TextElement(text) => Some(text.to_string()),
+                _ => None,
+            }
+        };
+        assert_eq!(text("a").as_ref().map(|s| &s[..]), Some("A"));
+        assert_eq!(text("a.title").as_ref().map(|s| &s[..]), Some("T"));
+        assert_eq!(text("b").as_ref().map(|s| &s[..]), Some("B"));
+        assert_eq!(text("c.x").as_ref().map(|s| &s[..]), Some("X"));
+        assert!(bundle.locate("c").is_some());
+        assert_eq!(bundle.pattern(bundle.locate("c").unwrap()), None);
+        assert_eq!(bundle.locate("a.nope"), None);
+        assert_eq!(bundle.locate("nope"), None);
+
+        let mut paths = bundle.paths().collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths, vec!["a", "a.title", "b", "c", "c.x"]);
+    }
+}
@@ -0,0 +1,208 @@
+//! A bounded LRU cache for negotiated locale chains.
+//!
+//! Most traffic sends a handful of distinct `Accept-Language` headers, so caching the result
+//! of negotiation turns `Localize::new` into a hash lookup in the common case.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use super::MAX_HEADER_LEN;
+
+/// The default number of locale chains cached by a `Catalog`.
+pub const LOCALE_CHAIN_CACHE_SIZE: usize = 256;
+
+/// Maps (user_locales, accept_language) to negotiated locale chains, as lists of locale indices.
+///
+/// Hits only take a read lock, so threads looking up cached chains don't block each other;
+/// recency is tracked with atomics instead.
+pub struct LocaleChainCache {
+    capacity: usize,
+
+    /// Entries, keyed by a hash of their inputs.
+    /// On collision, the newer entry replaces the older one.
+    entries: RwLock<HashMap<u64, Entry>>,
+
+    /// Incremented on every access; used to find the least recently used entry.
+    tick: AtomicU64,
+}
+
+struct Entry {
+    user_locales: Box<[Box<str>]>,
+    accept_language: Option<Box<str>>,
+    chain: Arc<[u16]>,
+    last_used: AtomicU64,
+}
+
+impl Entry {
+    fn matches(&self, user_locales: &[&str], accept_language: Option<&str>) -> bool {
+        self.accept_language.as_ref().map(|a| &a[..]) == accept_language
+            && self.user_locales.len() == user_locales.len()
+            && self
+                .user_locales
+                .iter()
+                .zip(user_locales)
+                .all(|(a, b)| &a[..] == *b)
+    }
+}
+
+impl LocaleChainCache {
+    /// Create a cache holding at most `capacity` chains. A capacity of 0 disables caching.
+    pub fn new(capacity: usize) -> LocaleChainCache {
+        LocaleChainCache {
+            capacity,
+            entries: RwLock::new(HashMap::with_capacity(capacity)),
+            tick: AtomicU64::new(0),
+        }
+    }
+
+    /// Look up a chain, or compute and cache it with `negotiate` if it isn't present.
+    pub fn get_or_insert_with<F>(
+        &self,
+        user_locales: &[&str],
+        accept_language: Option<&str>,
+        negotiate: F,
+    ) -> Arc<[u16]>
+    where
+        F: FnOnce() -> Vec<u16>,
+    {
+        if self.capacity == 0 || accept_language.map(str::len).unwrap_or(0) > MAX_HEADER_LEN {
+            // don't let oversized headers push out legitimate entries
+            return negotiate().into();
+        }
+
+        let key = hash_key(user_locales, accept_language);
+
+        if let Some(chain) = self.get(key, user_locales, accept_language) {
+            return chain;
+        }
+
+        // negotiate without holding the lock; racing threads may negotiate the same chain twice,
+        // which is harmless.
+        let chain: Arc<[u16]> = negotiate().into();
+        self.insert(key, user_locales, accept_language, chain.clone());
+        chain
+    }
+
+    /// The number of cached chains.
+    pub fn len(&self) -> usize {
+        self.entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Whether no chains are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(
+        &self,
+        key: u64,
+        user_locales: &[&str],
+        accept_language: Option<&str>,
+    ) -> Option<Arc<[u16]>> {
+        // the map is never left half-updated, so a panic elsewhere doesn't make it unusable
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+
+        let entry = entries.get(&key)?;
+        if !entry.matches(user_locales, accept_language) {
+            return None;
+        }
+        entry.last_used.store(self.next_tick(), Ordering::Relaxed);
+        Some(entry.chain.clone())
+    }
+
+    fn insert(
+        &self,
+        key: u64,
+        user_locales: &[&str],
+        accept_language: Option<&str>,
+        chain: Arc<[u16]>,
+    ) {
+        let mut entries = self.entries.write().unwrap_or_else(PoisonError::into_inner);
+        let tick = self.next_tick();
+
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            // evictions only happen on misses, which pay for a negotiation anyway,
+            // so a linear scan is fine.
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                user_locales: user_locales.iter().map(|l| Box::from(*l)).collect(),
+                accept_language: accept_language.map(Box::from),
+                chain,
+                last_used: AtomicU64::new(tick),
+            },
+        );
+    }
+
+    fn next_tick(&self) -> u64 {
+        self.tick.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+fn hash_key(user_locales: &[&str], accept_language: Option<&str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    user_locales.hash(&mut hasher);
+    accept_language.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru() {
+        let cache = LocaleChainCache::new(2);
+        let mut negotiations = 0;
+        let mut get = |cache: &LocaleChainCache, user: &[&str], header: Option<&str>| {
+            cache.get_or_insert_with(user, header, || {
+                negotiations += 1;
+                vec![0]
+            })
+        };
+
+        let a = get(&cache, &["es"], None);
+        let b = get(&cache, &["es"], None);
+        assert!(Arc::ptr_eq(&a, &b));
+
+        // same strings, different positions
+        get(&cache, &[], Some("es"));
+        assert_eq!(cache.len(), 2);
+
+        // evicts ([], Some("es")), since (["es"], None) was used more recently
+        get(&cache, &["es"], None);
+        get(&cache, &["de"], None);
+        assert_eq!(cache.len(), 2);
+        get(&cache, &["es"], None);
+        get(&cache, &[], Some("es"));
+
+        assert_eq!(negotiations, 4);
+    }
+
+    #[test]
+    fn limits() {
+        let cache = LocaleChainCache::new(0);
+        cache.get_or_insert_with(&[], Some("en"), || vec![0]);
+        assert_eq!(cache.len(), 0);
+
+        let cache = LocaleChainCache::new(16);
+        let header = "en,".repeat(MAX_HEADER_LEN);
+        cache.get_or_insert_with(&[], Some(&header), || vec![0]);
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -0,0 +1,31 @@
+//! Decompression of baked sources, for `impl_localize!`'s `#[compress(...)]` option.
+//!
+//! Each compression method is behind a feature (`with-zstd`, `with-deflate`), which also enables
+//! it in codegen. If a localizer asks for a method that isn't enabled, the generated code refers to
+//! a variant of `Compression` that doesn't exist, and fails to compile.
+
+/// A compression method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "with-zstd")]
+    Zstd,
+    #[cfg(feature = "with-deflate")]
+    Deflate,
+}
+
+/// Decompress baked data.
+pub fn decompress(compression: Compression, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    // unused if no methods are enabled, in which case there are no `Compression`s to match
+    let _ = data;
+    match compression {
+        #[cfg(feature = "with-zstd")]
+        Compression::Zstd => zstd::stream::decode_all(data),
+        #[cfg(feature = "with-deflate")]
+        Compression::Deflate => {
+            use std::io::Read;
+            let mut result = Vec::new();
+            flate2::read::DeflateDecoder::new(data).read_to_end(&mut result)?;
+            Ok(result)
+        }
+    }
+}
@@ -0,0 +1,1282 @@
+//! Code used in the implementation of `impl_localize!`.
+//!
+//! Everything in this module should be considered an internal implementation detail; it is only public
+//! for use by the macro. The exceptions are `Catalog` and `CatalogLocalizer`, which are re-exported
+//! from the crate root for building catalogs at runtime.
+//!
+//! Maintenance note: in general, the policy is to move as much i18n code as possible into here;
+//! whatever absolutely *must* be included in the generated code is done in baked_fluent_codegen.
+
+use fluent_bundle::{FluentResource, FluentValue};
+use fluent_locale::{negotiate_languages, NegotiationStrategy};
+use fluent_syntax::parser::ParserError;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+
+use log::{debug, info, warn};
+
+pub use lazy_static::lazy_static;
+
+use super::{Error, MessageId, Result};
+
+mod accept_language;
+mod bundle;
+mod cache;
+mod compress;
+mod resolve;
+
+pub use self::resolve::IoWriter;
+
+pub use self::accept_language::{
+    range_matches, AcceptLanguage, LanguageRange, MAX_HEADER_LEN, MAX_RANGES,
+};
+pub use self::cache::{LocaleChainCache, LOCALE_CHAIN_CACHE_SIZE};
+pub use self::compress::{decompress, Compression};
+
+use self::bundle::{Bundle, PatternRef};
+
+/// A set of translations, and everything needed to look messages up in them.
+///
+/// `impl_localize!` bakes one of these into a static for each generated localizer. They can also
+/// be built at runtime with `Catalog::new`, and used through `CatalogLocalizer`s.
+///
+/// A catalog owns all of its data, so it has no lifetime parameters and can be shared with
+/// `Arc`. Locales are identified by their index in the catalog's sources, and locale chains are
+/// passed around in two forms: a list of locale indices, in order of preference, and a bitmask of
+/// the same locales (see `mask_contains`). Messages are identified by `MessageId`s, their index
+/// in the sorted list of all message paths.
+///
+/// A Catalog is `Send + Sync`, and is shared by every thread that localizes anything.
+/// This is safe because bundles are never modified once loaded: messages are formatted
+/// by `resolve`, which keeps all of its state on the caller's stack, rather than by
+/// `FluentBundle::format`, so there are no memoized formatters to share. The only shared mutable
+/// state is the locale chain cache, which is synchronized internally, and the set of loaded
+/// locales in lazy catalogs, each of which is initialized at most once.
+pub struct Catalog {
+    /// Translations, indexed by locale. In lazy catalogs, each is loaded on first use.
+    loaded: Vec<OnceLock<Loaded>>,
+
+    /// Where to load translations from, for catalogs of baked sources, indexed by locale: each
+    /// namespace's sources, in order of precedence. Empty for catalogs built at runtime.
+    sources: Vec<Vec<(&'static str, &'static LocaleSources)>>,
+
+    /// Available locales (e.g. "en_US", not just "en"), indexed by locale.
+    available: Vec<Box<str>>,
+
+    /// Maps locale names to indices.
+    locale_ids: HashMap<Box<str>, u16>,
+
+    /// For each message and attribute, indexed by ID: its path, and a bitmask of the locales
+    /// that provide it.
+    messages: Vec<(Box<str>, Box<[u64]>)>,
+
+    /// Maps message paths to IDs.
+    message_ids: HashMap<Box<str>, MessageId>,
+
+    /// The default locale chosen if no others can be determined.
+    default_locale: u16,
+
+    /// Recently negotiated locale chains.
+    chain_cache: LocaleChainCache,
+}
+
+/// The translations for one locale.
+struct Loaded {
+    /// A bundle for each namespace the locale has translations in; the root namespace is `""`.
+    /// Messages in a namespace are addressed as `namespace.message`.
+    bundles: Vec<(Box<str>, Bundle)>,
+
+    /// Where to find each message this locale provides, indexed by message ID: the index of its
+    /// bundle, and its location in that bundle.
+    patterns: Box<[Option<(u16, PatternRef)>]>,
+}
+
+/// An error building a `Catalog` at runtime.
+#[derive(Debug)]
+pub enum CatalogError {
+    /// A source failed to parse.
+    Parse {
+        locale: String,
+        errors: Vec<ParserError>,
+    },
+    /// The default locale isn't one of the catalog's locales.
+    MissingDefaultLocale(String),
+    /// More than `u16::MAX` locales, or `u32::MAX` messages.
+    TooLarge,
+}
+impl std::error::Error for CatalogError {}
+impl std::fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CatalogError::Parse {
+                ref locale,
+                ref errors,
+            } => write!(f, "{} parse errors in locale {}", errors.len(), locale),
+            CatalogError::MissingDefaultLocale(ref locale) => {
+                write!(f, "no translations for default locale {}", locale)
+            }
+            CatalogError::TooLarge => write!(f, "too many locales or messages"),
+        }
+    }
+}
+
+impl Catalog {
+    /// Build a catalog from fluent sources.
+    /// * `sources`: for each locale (e.g. "en_US"), the contents of its `.ftl` files.
+    /// * `default_locale`: the locale to use if no others can be chosen; must be in `sources`.
+    pub fn new(
+        sources: Vec<(String, Vec<String>)>,
+        default_locale: &str,
+    ) -> std::result::Result<Catalog, CatalogError> {
+        let mut available = Vec::new();
+        let mut bundles = Vec::new();
+        for (locale, sources) in sources {
+            let mut resources = Vec::new();
+            for source in sources {
+                match FluentResource::try_new(source) {
+                    Ok(resource) => resources.push(resource),
+                    Err((_, errors)) => return Err(CatalogError::Parse { locale, errors }),
+                }
+            }
+            bundles.push(Bundle::new(&locale, resources));
+            available.push(locale.into_boxed_str());
+        }
+
+        let messages = index_messages(&bundles);
+        let catalog = Catalog::build(available, messages, Vec::new(), default_locale)?;
+        for (locale, bundle) in bundles.into_iter().enumerate() {
+            let loaded = catalog.prepare(locale, vec![(Box::from(""), bundle)]);
+            let _ = catalog.loaded[locale].set(loaded);
+        }
+        Ok(catalog)
+    }
+
+    /// Build the catalog for sources baked in by `impl_localize!`, whose messages have already
+    /// been indexed.
+    /// * `included`: the sources of other localizers, from `#[include_sources(...)]`, in order.
+    ///   They're merged into the locales `sources` has; later ones take precedence over earlier
+    ///   ones, and `sources` over all of them. Their messages get IDs after those in `messages`.
+    /// * `lazy`: if set, each locale is only parsed when it's first needed (or warmed up);
+    ///   otherwise, all locales are parsed immediately.
+    #[doc(hidden)]
+    pub fn from_static(
+        sources: Sources,
+        messages: Messages,
+        included: &[ExportedSources],
+        default_locale: &str,
+        lazy: bool,
+    ) -> Catalog {
+        let available = sources
+            .iter()
+            .map(|(locale, _)| Box::from(*locale))
+            .collect::<Vec<_>>();
+        let mut locale_sources = sources
+            .iter()
+            .map(|(_, namespaces)| {
+                namespaces
+                    .iter()
+                    .map(|(namespace, sources)| (*namespace, sources))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let baked = messages;
+        let mut messages = baked
+            .iter()
+            .map(|(path, mask)| (Box::<str>::from(*path), Box::<[u64]>::from(*mask)))
+            .collect::<Vec<_>>();
+
+        if !included.is_empty() {
+            let mask_words = available.len().div_ceil(64);
+            let ids = messages
+                .iter()
+                .enumerate()
+                .map(|(id, (path, _))| (path.clone(), id))
+                .collect::<HashMap<_, _>>();
+            let mut extra = BTreeMap::new();
+            let namespaces = sources
+                .iter()
+                .chain(included.iter().flat_map(|exported| exported.sources))
+                .flat_map(|(_, namespaces)| namespaces.iter())
+                .map(|(namespace, _)| *namespace)
+                .filter(|namespace| !namespace.is_empty())
+                .collect::<BTreeSet<_>>();
+            // `check_definitions` rejects these within one localizer, but can't see across them
+            let roots = baked
+                .iter()
+                .chain(included.iter().flat_map(|exported| exported.messages))
+                .map(|(path, _)| *path);
+            for namespace in namespace_clashes(&namespaces, roots) {
+                warn!(
+                    "{} is both a namespace and a message; paths starting with {}. look in the \
+                     namespace first",
+                    namespace, namespace
+                );
+            }
+
+            // bundles keep the first definition of each message, so an included message (and
+            // its attributes) only counts if nothing with precedence over it defines one too
+            let mut defined = HashSet::new();
+            for (path, mask) in baked {
+                if message_path(&namespaces, path) == *path {
+                    for locale in 0..available.len() {
+                        if mask_contains(mask, locale as u16) {
+                            defined.insert((locale, *path));
+                        }
+                    }
+                }
+            }
+
+            for exported in included.iter().rev() {
+                // the index of each exported locale in `available`, if it's there
+                let locales = exported
+                    .sources
+                    .iter()
+                    .map(|(locale, _)| available.iter().position(|name| &**name == *locale))
+                    .collect::<Vec<_>>();
+                for ((locale, namespaces), ours) in exported.sources.iter().zip(&locales) {
+                    match ours {
+                        Some(ours) => locale_sources[*ours].extend(
+                            namespaces
+                                .iter()
+                                .map(|(namespace, sources)| (*namespace, sources)),
+                        ),
+                        None => debug!("leaving out included locale {}: not available", locale),
+                    }
+                }
+                let mut won = Vec::new();
+                for (path, exported_mask) in exported.messages {
+                    let message = message_path(&namespaces, path);
+                    let providers = locales
+                        .iter()
+                        .enumerate()
+                        .filter(|(theirs, _)| mask_contains(exported_mask, *theirs as u16))
+                        .filter_map(|(_, ours)| *ours)
+                        .filter(|ours| !defined.contains(&(*ours, message)));
+                    for locale in providers {
+                        if message == *path {
+                            won.push((locale, message));
+                        }
+                        let mask = match ids.get(*path) {
+                            Some(id) => &mut messages[*id].1,
+                            None => extra
+                                .entry(Box::from(*path))
+                                .or_insert_with(|| vec![0u64; mask_words].into_boxed_slice()),
+                        };
+                        mask[locale / 64] |= 1 << (locale % 64);
+                    }
+                }
+                defined.extend(won);
+            }
+
+            // messages only included sources have come after the baked ones, so that the IDs of
+            // those don't change
+            messages.extend(extra);
+        }
+
+        let catalog = Catalog::build(available, messages, locale_sources, default_locale)
+            .expect("invalid baked catalog");
+
+        if !lazy {
+            info!("parsing embedded fluent sources");
+            for locale in 0..catalog.loaded.len() {
+                catalog.load(locale);
+            }
+            info!("done, bundle count: {}", catalog.loaded.len());
+        }
+        catalog
+    }
+
+    fn build(
+        available: Vec<Box<str>>,
+        messages: Vec<(Box<str>, Box<[u64]>)>,
+        sources: Vec<Vec<(&'static str, &'static LocaleSources)>>,
+        default_locale: &str,
+    ) -> std::result::Result<Catalog, CatalogError> {
+        if available.len() > u16::MAX as usize || messages.len() > u32::MAX as usize {
+            return Err(CatalogError::TooLarge);
+        }
+
+        let locale_ids = available
+            .iter()
+            .enumerate()
+            .map(|(id, locale)| (locale.clone(), id as u16))
+            .collect::<HashMap<_, _>>();
+        let default_locale = match locale_ids.get(default_locale) {
+            Some(id) => *id,
+            None => return Err(CatalogError::MissingDefaultLocale(default_locale.into())),
+        };
+        let message_ids = messages
+            .iter()
+            .enumerate()
+            .map(|(id, (path, _))| (path.clone(), MessageId::new(id as u32)))
+            .collect();
+
+        Ok(Catalog {
+            loaded: available.iter().map(|_| OnceLock::new()).collect(),
+            sources,
+            available,
+            locale_ids,
+            messages,
+            message_ids,
+            default_locale,
+            chain_cache: LocaleChainCache::new(LOCALE_CHAIN_CACHE_SIZE),
+        })
+    }
+
+    /// Get the translations for a locale, loading them if necessary.
+    fn load(&self, locale: usize) -> &Loaded {
+        self.loaded[locale].get_or_init(|| {
+            let name = &self.available[locale];
+            let sources = self
+                .sources
+                .get(locale)
+                .expect("invariant violated: runtime catalogs are loaded eagerly");
+            info!(
+                "parsing locale: {}, {} sources",
+                name,
+                sources
+                    .iter()
+                    .map(|(_, sources)| sources.len())
+                    .sum::<usize>()
+            );
+
+            // the same namespace can come from several localizers; bundles keep the first
+            // definition of each message, so their sources are concatenated in order of precedence
+            let mut namespaces: Vec<(&str, Vec<FluentResource>)> = Vec::new();
+            for (namespace, sources) in sources {
+                let i = match namespaces.iter().position(|(n, _)| n == namespace) {
+                    Some(i) => i,
+                    None => {
+                        namespaces.push((namespace, Vec::new()));
+                        namespaces.len() - 1
+                    }
+                };
+                namespaces[i]
+                    .1
+                    .extend(sources.decode().into_iter().map(|source| {
+                        FluentResource::try_new(source)
+                            .expect("baked .ftl translation failed to parse")
+                    }));
+            }
+            let bundles = namespaces
+                .into_iter()
+                .map(|(namespace, resources)| (Box::from(namespace), Bundle::new(name, resources)))
+                .collect();
+            self.prepare(locale, bundles)
+        })
+    }
+
+    /// Find every message a locale provides in its bundles.
+    fn prepare(&self, locale: usize, bundles: Vec<(Box<str>, Bundle)>) -> Loaded {
+        let patterns = self
+            .messages
+            .iter()
+            .map(|(path, providers)| {
+                if !mask_contains(providers, locale as u16) {
+                    return None;
+                }
+                let pattern = locate(&bundles, path);
+                if pattern.is_none() {
+                    warn!(
+                        "invariant violated: message index says {} provides {}",
+                        self.available[locale], path
+                    );
+                }
+                pattern
+            })
+            .collect();
+        Loaded { bundles, patterns }
+    }
+
+    /// Load the translations for some locales ahead of time, so that the first requests for them
+    /// don't pay for parsing. Only useful for lazy catalogs; everything else is loaded already.
+    /// * `locales`: locale names; short-form locales (e.g. "en") warm up every matching locale.
+    pub fn warm_up(&self, locales: &[&str]) {
+        for requested in locales {
+            let mut found = false;
+            for (locale, name) in self.available.iter().enumerate() {
+                if range_matches(requested, name) {
+                    self.load(locale);
+                    found = true;
+                }
+            }
+            if !found {
+                warn!("can't warm up {}: no matching locales", requested);
+            }
+        }
+    }
+
+    /// Whether a locale's translations have been loaded.
+    pub fn is_loaded(&self, locale: &str) -> bool {
+        match self.locale_id(locale) {
+            Some(locale) => self.loaded[locale as usize].get().is_some(),
+            None => false,
+        }
+    }
+
+    /// Create a localizer for this catalog. See `Localize::new` for the meaning of the arguments.
+    pub fn localizer(
+        self: &Arc<Self>,
+        user_locales: &[&str],
+        accept_language: Option<&str>,
+    ) -> CatalogLocalizer {
+        let locales = self.locale_chain(user_locales, accept_language);
+        let mut mask = vec![0; self.available.len().div_ceil(64)].into_boxed_slice();
+        for locale in locales.iter() {
+            mask[*locale as usize / 64] |= 1 << (locale % 64);
+        }
+        CatalogLocalizer {
+            catalog: self.clone(),
+            locales,
+            mask,
+        }
+    }
+
+    /// The index of a locale, if it's available.
+    pub fn locale_id(&self, locale: &str) -> Option<u16> {
+        self.locale_ids.get(locale).cloned()
+    }
+
+    /// The name of a locale, by index.
+    pub fn locale(&self, locale: u16) -> &str {
+        &self.available[locale as usize]
+    }
+
+    /// Like `create_locale_chain`, but returns locale indices, from a cache of recent
+    /// negotiations if possible.
+    pub fn locale_chain(&self, user_locales: &[&str], accept_language: Option<&str>) -> Arc<[u16]> {
+        self.chain_cache
+            .get_or_insert_with(user_locales, accept_language, || {
+                self.negotiate(user_locales, accept_language)
+            })
+    }
+
+    /// Like `locale_chain`, but writes the chain into caller-provided buffers and returns its
+    /// length, so that localizers can be created without allocating.
+    /// * `locales`: receives the indices of the locales in the chain. Must have room for every
+    ///   available locale.
+    /// * `mask`: receives their bitmask. Must have `(available locales + 63) / 64` words.
+    pub fn fill_locale_chain(
+        &self,
+        user_locales: &[&str],
+        accept_language: Option<&str>,
+        locales: &mut [u16],
+        mask: &mut [u64],
+    ) -> usize {
+        let negotiated = self.locale_chain(user_locales, accept_language);
+        for word in mask.iter_mut() {
+            *word = 0;
+        }
+        for (i, locale) in negotiated.iter().enumerate() {
+            locales[i] = *locale;
+            mask[*locale as usize / 64] |= 1 << (locale % 64);
+        }
+        negotiated.len()
+    }
+
+    /// Creates a chain of locales to use for message lookups.
+    /// * `user_locales`: a list of locales allowed by the user,
+    ///   in descending order of preference.
+    ///    - May be empty.
+    ///    - May be short-form locales (e.g. "en")
+    /// * `accept_language`: an `Accept-Language` header, if present.
+    ///   See `AcceptLanguage` for how it's interpreted.
+    ///
+    /// `user_locales` take precedence over the header, and aren't affected by `q=0` exclusions
+    /// in it. The default locale is always the last resort, even if it's excluded.
+    pub fn create_locale_chain(
+        &self,
+        user_locales: &[&str],
+        accept_language: Option<&str>,
+    ) -> Vec<&str> {
+        self.negotiate(user_locales, accept_language)
+            .into_iter()
+            .map(|locale| self.locale(locale))
+            .collect()
+    }
+
+    fn negotiate(&self, user_locales: &[&str], accept_language: Option<&str>) -> Vec<u16> {
+        let accept_language = accept_language.map(AcceptLanguage::parse);
+        let default_locale = self.locale(self.default_locale);
+
+        let mut requested = user_locales.to_owned();
+        if let Some(ref accept_language) = accept_language {
+            if accept_language.truncated || !accept_language.invalid.is_empty() {
+                debug!("malformed Accept-Language header: {:?}", accept_language);
+            }
+            requested.extend(accept_language.ranges.iter().map(|range| {
+                if range.is_wildcard() {
+                    // any language will do, so use the one we have the most of
+                    default_locale
+                } else {
+                    range.range
+                }
+            }));
+        }
+
+        let result = negotiate_languages(
+            &requested,
+            &self.available,
+            Some(default_locale),
+            &NegotiationStrategy::Filtering,
+        );
+
+        let excluded = |locale: &str| match accept_language {
+            Some(ref accept_language) => {
+                locale != default_locale
+                    && accept_language.excludes(locale)
+                    && !user_locales
+                        .iter()
+                        .any(|requested| range_matches(requested, locale))
+            }
+            None => false,
+        };
+
+        result
+            .into_iter()
+            .filter(|l| !excluded(l))
+            .map(|l| {
+                *self
+                    .locale_ids
+                    .get(l)
+                    .expect("invariant violated: available and locale_ids have same contents")
+            })
+            .collect()
+    }
+
+    /// Look up the ID of a message or attribute.
+    pub fn message_id(&self, message: &str) -> Option<MessageId> {
+        self.message_ids.get(message).cloned()
+    }
+
+    /// Localize a message.
+    /// * `locales`: the indices of a chain of locales, in descending order of preference
+    /// * `message`: a message ID
+    /// * `args`: a slice of arguments to pass to Fluent.
+    pub fn localize_into<W: std::fmt::Write>(
+        &self,
+        writer: &mut W,
+        locales: &[u16],
+        message: MessageId,
+        args: &[(&str, &FluentValue)],
+    ) -> Result<()> {
+        let (path, providers) = match self.messages.get(message.index()) {
+            Some((path, providers)) => (&path[..], &providers[..]),
+            None => ("<unknown message>", &[][..]),
+        };
+        let provider = locales
+            .iter()
+            .find(|locale| mask_contains(providers, **locale));
+
+        if let Some(locale) = provider {
+            let loaded = self.load(*locale as usize);
+            if let Some((bundle, pattern)) = loaded.patterns[message.index()] {
+                let bundle = &loaded.bundles[bundle as usize].1;
+
+                // writes directly into `writer`
+                let errs = resolve::format_pattern_into(
+                    writer,
+                    bundle,
+                    path,
+                    bundle.pattern(pattern),
+                    args,
+                )?;
+                for err in errs {
+                    warn!("fluent localization error (ignored): {:?}", err);
+                }
+                return Ok(());
+            }
+        }
+
+        // nowhere to fall back to
+        Err(Error::NoTranslations {
+            message: path.to_string(),
+            locale_chain: locales
+                .iter()
+                .map(|locale| self.locale(*locale).to_string())
+                .collect(),
+        })
+    }
+
+    /// Whether any locale in a chain provides a message.
+    /// * `mask`: the bitmask of the chain's locales
+    pub fn has_message(&self, mask: &[u64], message: MessageId) -> bool {
+        match self.messages.get(message.index()) {
+            Some((_, providers)) => providers.iter().zip(mask).any(|(a, b)| a & b != 0),
+            None => false,
+        }
+    }
+}
+
+/// Find a message or attribute path in the bundles of a locale. Namespaced messages
+/// (`namespace.message[.attribute]`) are looked up in their namespace's bundle, and everything
+/// else in the root bundle; `impl_localize!` makes sure namespaces and root messages don't share
+/// names.
+fn locate(bundles: &[(Box<str>, Bundle)], path: &str) -> Option<(u16, PatternRef)> {
+    let namespaced = bundles
+        .iter()
+        .enumerate()
+        .filter(|(_, (namespace, _))| !namespace.is_empty())
+        .find_map(|(i, (namespace, bundle))| {
+            let rest = path.strip_prefix(&namespace[..])?.strip_prefix('.')?;
+            Some((i as u16, bundle.locate(rest)?))
+        });
+    namespaced.or_else(|| {
+        let i = bundles
+            .iter()
+            .position(|(namespace, _)| namespace.is_empty())?;
+        Some((i as u16, bundles[i].1.locate(path)?))
+    })
+}
+
+/// The path of the message a message or attribute path belongs to, split the way `locate`
+/// splits it: `namespace.message` for namespaced messages, and `message` for everything else.
+fn message_path<'p>(namespaces: &BTreeSet<&str>, path: &'p str) -> &'p str {
+    let start = namespaces
+        .iter()
+        .find(|namespace| {
+            path.strip_prefix(**namespace)
+                .is_some_and(|rest| rest.starts_with('.'))
+        })
+        .map_or(0, |namespace| namespace.len() + 1);
+    match path[start..].find('.') {
+        Some(dot) => &path[..start + dot],
+        None => path,
+    }
+}
+
+/// The namespaces that are also the IDs of messages outside any namespace, given the paths of
+/// every message and attribute.
+fn namespace_clashes<'n>(
+    namespaces: &BTreeSet<&'n str>,
+    paths: impl Iterator<Item = &'static str>,
+) -> BTreeSet<&'n str> {
+    // only root messages have paths without a dot
+    let roots = paths
+        .filter(|path| !path.contains('.'))
+        .collect::<HashSet<_>>();
+    namespaces
+        .iter()
+        .filter(|namespace| roots.contains(**namespace))
+        .copied()
+        .collect()
+}
+
+/// Index the messages of bundles, for catalogs that weren't indexed at compile time.
+/// Must agree with `baked_fluent_codegen`'s `MessageIndex`.
+fn index_messages(bundles: &[Bundle]) -> Vec<(Box<str>, Box<[u64]>)> {
+    let mask_words = bundles.len().div_ceil(64);
+    let mut messages = BTreeMap::new();
+    for (locale, bundle) in bundles.iter().enumerate() {
+        for path in bundle.paths() {
+            let mask = messages
+                .entry(path)
+                .or_insert_with(|| vec![0u64; mask_words]);
+            mask[locale / 64] |= 1 << (locale % 64);
+        }
+    }
+    messages
+        .into_iter()
+        .map(|(path, mask)| (path.into_boxed_str(), mask.into_boxed_slice()))
+        .collect()
+}
+
+/// A localizer for a `Catalog` built at runtime.
+///
+/// This is the runtime counterpart of the localizers generated by `impl_localize!`: it holds a
+/// reference to its catalog, so it's `Send + Sync + 'static`, and can be kept across `.await`
+/// points or moved to other threads.
+#[derive(Clone)]
+pub struct CatalogLocalizer {
+    catalog: Arc<Catalog>,
+    locales: Arc<[u16]>,
+    mask: Box<[u64]>,
+}
+
+impl CatalogLocalizer {
+    /// Localize a particular message.
+    pub fn localize(&self, message_id: &str, args: &[(&str, &FluentValue)]) -> Result<String> {
+        let mut result = String::new();
+        self.localize_into(&mut result, message_id, args)?;
+        Ok(result)
+    }
+
+    /// Localize a particular message into a std::fmt::Write.
+    pub fn localize_into<W: std::fmt::Write>(
+        &self,
+        writer: &mut W,
+        message_id: &str,
+        args: &[(&str, &FluentValue)],
+    ) -> Result<()> {
+        match self.catalog.message_id(message_id) {
+            Some(id) => self.localize_id_into(writer, id, args),
+            None => Err(Error::NoTranslations {
+                message: message_id.to_string(),
+                locale_chain: self.locale_chain().map(String::from).collect(),
+            }),
+        }
+    }
+
+    /// Localize a particular message, by ID, into a std::fmt::Write.
+    /// IDs can be found with `Catalog::message_id`.
+    pub fn localize_id_into<W: std::fmt::Write>(
+        &self,
+        writer: &mut W,
+        message: MessageId,
+        args: &[(&str, &FluentValue)],
+    ) -> Result<()> {
+        self.catalog
+            .localize_into(writer, &self.locales, message, args)
+    }
+
+    /// Whether this localizer has a particular message available, in any locale of its chain.
+    pub fn has_message(&self, message_id: &str) -> bool {
+        match self.catalog.message_id(message_id) {
+            Some(id) => self.catalog.has_message(&self.mask, id),
+            None => false,
+        }
+    }
+
+    /// The chain of locales this localizer looks up messages in.
+    pub fn locale_chain<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.locales
+            .iter()
+            .map(move |locale| self.catalog.locale(*locale))
+    }
+
+    /// The catalog this localizer looks up messages in.
+    pub fn catalog(&self) -> &Arc<Catalog> {
+        &self.catalog
+    }
+}
+
+// Catalogs live in statics, and localizers are held across threads and `.await`s, so they must
+// stay `Send + Sync`. Checking that here means a regression fails to compile in this crate,
+// instead of in every crate using `impl_localize!`.
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn check<T: Send + Sync + 'static>() {}
+    check::<Catalog>();
+    check::<CatalogLocalizer>();
+}
+
+/// Whether a bitmask of locales contains a locale.
+pub fn mask_contains(mask: &[u64], locale: u16) -> bool {
+    match mask.get(locale as usize / 64) {
+        Some(word) => word & (1 << (locale % 64)) != 0,
+        None => false,
+    }
+}
+
+/// Sources; an array mapping &'static strs to fluent source strings. Instantiated only by the `impl_localize!` macro.
+/// Each locale's sources are grouped by namespace (`""` for the root namespace); each group is
+/// loaded into its own bundle, so terms and message references don't cross namespaces.
+pub type Sources = &'static [(&'static str, &'static [(&'static str, LocaleSources)])];
+
+/// The sources and message index of a localizer, exported with `#[export_sources(NAME)]` so that
+/// other localizers can include them with `#[include_sources(path::to::NAME)]`.
+pub struct ExportedSources {
+    pub sources: Sources,
+    pub messages: Messages,
+}
+
+/// The baked sources of a single namespace of a single locale.
+pub enum LocaleSources {
+    /// The contents of each file.
+    Plain(&'static [&'static str]),
+    /// The contents of all files, concatenated and compressed.
+    Compressed {
+        compression: Compression,
+        data: &'static [u8],
+        /// The length of each file, in bytes.
+        lengths: &'static [usize],
+    },
+}
+
+impl LocaleSources {
+    /// The number of files.
+    pub fn len(&self) -> usize {
+        match *self {
+            LocaleSources::Plain(sources) => sources.len(),
+            LocaleSources::Compressed { lengths, .. } => lengths.len(),
+        }
+    }
+
+    /// Whether there are no files.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The contents of each file, decompressed if necessary.
+    pub fn decode(&self) -> Vec<String> {
+        match *self {
+            LocaleSources::Plain(sources) => sources.iter().map(|s| s.to_string()).collect(),
+            LocaleSources::Compressed {
+                compression,
+                data,
+                lengths,
+            } => {
+                let data = decompress(compression, data).expect("baked sources are corrupt");
+                let mut data = &data[..];
+                lengths
+                    .iter()
+                    .map(|length| {
+                        let (source, rest) = data.split_at(*length);
+                        data = rest;
+                        String::from_utf8(source.to_vec()).expect("baked sources are corrupt")
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Messages; an array of message IDs (and `message.attribute` paths), sorted, along with bitmasks
+/// of the locales in the corresponding `Sources` that provide them. A message's index in this
+/// array is its `MessageId`. Instantiated only by the `impl_localize!` macro.
+pub type Messages = &'static [(&'static str, &'static [u64])];
+
+pub use fluent_bundle::FluentValue as I18nValue;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCES: Sources = &[
+        (
+            "en_US",
+            &[(
+                "",
+                LocaleSources::Plain(&[r#"
+greeting = Hello, { $name }! You are { $hours } hours old.
+goodbye = Goodbye.
+"#]),
+            )],
+        ),
+        (
+            "en_AU",
+            &[(
+                "",
+                LocaleSources::Plain(&[r#"
+greeting = G'day, { $name }! You are { $hours } hours old.
+goodbye = Hooroo.
+"#]),
+            )],
+        ),
+        (
+            "es_MX",
+            &[(
+                "",
+                LocaleSources::Plain(&[r#"
+greeting = ¡Hola, { $name }! Tienes { $hours } horas.
+goodbye = Adiós.
+"#]),
+            )],
+        ),
+        (
+            "de_DE",
+            &[(
+                "",
+                LocaleSources::Plain(&[r#"
+greeting = Hallo { $name }! Du bist { $hours } Stunden alt.
+    .title = Begrüßung
+"#]),
+            )],
+        ),
+    ];
+
+    const MESSAGES: Messages = &[
+        ("goodbye", &[0b0111]),
+        ("greeting", &[0b1111]),
+        ("greeting.title", &[0b1000]),
+    ];
+
+    #[test]
+    fn basic() -> Result<()> {
+        let _ = pretty_env_logger::try_init();
+
+        let bundles = Catalog::from_static(SOURCES, MESSAGES, &[], "en_US", false);
+        let name = FluentValue::from("Jamie");
+        let hours = FluentValue::from(190321.31);
+        let args = &[("name", &name), ("hours", &hours)][..];
+
+        let ids = |locales: &[&str]| -> Vec<u16> {
+            locales
+                .iter()
+                .map(|locale| bundles.locale_id(locale).unwrap())
+                .collect()
+        };
+
+        let id = |message: &str| {
+            bundles
+                .message_id(message)
+                .unwrap_or(MessageId::new(u32::MAX))
+        };
+
+        let mut result = String::new();
+        bundles.localize_into(&mut result, &ids(&["en_US"]), id("greeting"), args)?;
+        assert_eq!(result, "Hello, Jamie! You are 190321.31 hours old.");
+        result.clear();
+
+        bundles.localize_into(&mut result, &ids(&["es_MX"]), id("greeting"), args)?;
+        assert_eq!(result, "¡Hola, Jamie! Tienes 190321.31 horas.");
+        result.clear();
+
+        bundles.localize_into(&mut result, &ids(&["de_DE"]), id("greeting"), args)?;
+        assert_eq!(result, "Hallo Jamie! Du bist 190321.31 Stunden alt.");
+        result.clear();
+
+        bundles.localize_into(
+            &mut result,
+            &ids(&["de_DE", "es_MX", "en_US"]),
+            id("goodbye"),
+            &[],
+        )?;
+        // missing messages should fall back to first available
+        assert_eq!(result, "Adiós.");
+        result.clear();
+
+        bundles.localize_into(
+            &mut result,
+            &ids(&["en_US", "de_DE"]),
+            id("greeting.title"),
+            &[],
+        )?;
+        // attributes are looked up like messages
+        assert_eq!(result, "Begrüßung");
+        result.clear();
+
+        assert!(
+            bundles
+                .localize_into(&mut result, &ids(&["en_US"]), id("bananas"), &[])
+                .is_err(),
+            "Should return Err on missing message"
+        );
+        assert!(
+            bundles
+                .localize_into(&mut result, &ids(&["en_US"]), id("greeting.title"), &[])
+                .is_err(),
+            "Should return Err on missing attribute"
+        );
+        result.clear();
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_locale_chain() {
+        let _ = pretty_env_logger::try_init();
+
+        let bundles = Catalog::from_static(SOURCES, MESSAGES, &[], "en_US", false);
+
+        // accept-language parser works + short-code lookup works
+        assert_eq!(
+            bundles.create_locale_chain(&[], Some("en_US, es_MX; q=0.5")),
+            &["en_US", "en_AU", "es_MX"]
+        );
+
+        // first choice has precedence
+        assert_eq!(
+            bundles.create_locale_chain(&["es_MX"], Some("en_US; q=0.5")),
+            &["es_MX", "en_US", "en_AU"]
+        );
+
+        // short codes work
+        assert_eq!(
+            bundles.create_locale_chain(&[], Some("en")),
+            &["en_US", "en_AU"]
+        );
+
+        // default works
+        assert_eq!(bundles.create_locale_chain(&[], None), &["en_US"]);
+
+        // missing languages fall through to default
+        assert_eq!(
+            bundles.create_locale_chain(&["zh_HK"], Some("xy_ZW")),
+            &["en_US"]
+        );
+
+        // weights are respected, regardless of header order
+        assert_eq!(
+            bundles.create_locale_chain(&[], Some("en_US;q=0.2, de_DE;q=0.9, es_MX")),
+            &["es_MX", "de_DE", "en_US", "en_AU"]
+        );
+
+        // wildcards mean the default locale
+        assert_eq!(
+            bundles.create_locale_chain(&[], Some("de_DE, *;q=0.5, es_MX;q=0.1")),
+            &["de_DE", "en_US", "en_AU", "es_MX"]
+        );
+
+        // q=0 excludes locales from the header...
+        assert_eq!(
+            bundles.create_locale_chain(&[], Some("*, en_AU;q=0")),
+            &["en_US"]
+        );
+        assert_eq!(
+            bundles.create_locale_chain(&[], Some("es, en;q=0")),
+            &["es_MX", "en_US"]
+        );
+        // ...but not ones the app asked for explicitly
+        assert_eq!(
+            bundles.create_locale_chain(&["en_AU"], Some("en;q=0")),
+            &["en_AU", "en_US"]
+        );
+
+        // malformed entries are skipped
+        assert_eq!(
+            bundles.create_locale_chain(&[], Some("es_MX;q=banana, de_DE;;, en_AU")),
+            &["en_AU", "en_US"]
+        );
+    }
+
+    #[test]
+    fn locale_chain_cache() {
+        let _ = pretty_env_logger::try_init();
+
+        let bundles = Catalog::from_static(SOURCES, MESSAGES, &[], "en_US", false);
+
+        let a = bundles.locale_chain(&["es_MX"], Some("de_DE"));
+        assert_eq!(&a[..], &[2, 3, 0]);
+        let b = bundles.locale_chain(&["es_MX"], Some("de_DE"));
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let c = bundles.locale_chain(&["es_MX"], None);
+        assert_eq!(&c[..], &[2, 0]);
+
+        let mut locales = [0; 4];
+        let mut mask = [0; 1];
+        let len = bundles.fill_locale_chain(&["es_MX"], Some("de_DE"), &mut locales, &mut mask);
+        assert_eq!(&locales[..len], &a[..]);
+        assert_eq!(mask, [0b1101]);
+    }
+
+    #[test]
+    fn has_message() {
+        let bundles = Catalog::from_static(SOURCES, MESSAGES, &[], "en_US", false);
+        let id = |message: &str| {
+            bundles
+                .message_id(message)
+                .unwrap_or(MessageId::new(u32::MAX))
+        };
+
+        assert_eq!(bundles.message_id("goodbye"), Some(MessageId::new(0)));
+        assert_eq!(
+            bundles.message_id("greeting.title"),
+            Some(MessageId::new(2))
+        );
+        assert_eq!(bundles.message_id("bananas"), None);
+
+        assert!(bundles.has_message(&[0b0001], id("goodbye")));
+        assert!(!bundles.has_message(&[0b1000], id("goodbye")));
+        assert!(bundles.has_message(&[0b1001], id("greeting.title")));
+        assert!(!bundles.has_message(&[0b0111], id("greeting.title")));
+        assert!(!bundles.has_message(&[0b1111], id("bananas")));
+    }
+
+    #[test]
+    fn runtime_catalog() -> Result<()> {
+        let _ = pretty_env_logger::try_init();
+
+        let owned = |sources: Sources| {
+            sources
+                .iter()
+                .map(|(locale, namespaces)| (locale.to_string(), namespaces[0].1.decode()))
+                .collect::<Vec<_>>()
+        };
+
+        // indexing at runtime agrees with indexing at compile time
+        let catalog = Arc::new(Catalog::new(owned(SOURCES), "en_US").unwrap());
+        let baked = Catalog::from_static(SOURCES, MESSAGES, &[], "en_US", false);
+        assert_eq!(catalog.messages, baked.messages);
+
+        let localizer = catalog.localizer(&[], Some("de-DE, es-MX;q=0.5"));
+        assert_eq!(
+            localizer.locale_chain().collect::<Vec<_>>(),
+            &["de_DE", "es_MX", "en_US"]
+        );
+        assert_eq!(localizer.localize("goodbye", &[])?, "Adiós.");
+        assert_eq!(localizer.localize("greeting.title", &[])?, "Begrüßung");
+        assert!(localizer.has_message("greeting.title"));
+        assert!(!localizer.has_message("bananas"));
+        match localizer.localize("bananas", &[]) {
+            Err(Error::NoTranslations {
+                message,
+                locale_chain,
+            }) => {
+                assert_eq!(message, "bananas");
+                assert_eq!(locale_chain.len(), 3);
+            }
+            other => panic!("expected NoTranslations, got {:?}", other),
+        }
+
+        match Catalog::new(owned(SOURCES), "fr_FR") {
+            Err(CatalogError::MissingDefaultLocale(locale)) => assert_eq!(locale, "fr_FR"),
+            _ => panic!("expected MissingDefaultLocale"),
+        }
+        match Catalog::new(vec![("en_US".into(), vec!["a = {".into()])], "en_US") {
+            Err(CatalogError::Parse { locale, .. }) => assert_eq!(locale, "en_US"),
+            _ => panic!("expected Parse"),
+        }
+
+        // duplicate definitions aren't caught here; the first one wins
+        let sources = vec!["a = A\n-t = T".into(), "a = B\n-t = U\nb = { -t }".into()];
+        let catalog = Arc::new(Catalog::new(vec![("en_US".into(), sources)], "en_US").unwrap());
+        let localizer = catalog.localizer(&[], None);
+        assert_eq!(localizer.localize("a", &[])?, "A");
+        assert_eq!(localizer.localize("b", &[])?, "T");
+
+        Ok(())
+    }
+
+    #[test]
+    fn lazy() -> Result<()> {
+        let _ = pretty_env_logger::try_init();
+
+        let catalog = Catalog::from_static(SOURCES, MESSAGES, &[], "en_US", true);
+        assert!(!catalog.is_loaded("en_US"));
+
+        // negotiation and message lookups don't need translations
+        let locales = catalog.locale_chain(&["es_MX", "de_DE"], None);
+        let mut mask = [0; 1];
+        catalog.fill_locale_chain(&["es_MX", "de_DE"], None, &mut [0; 4], &mut mask);
+        assert!(catalog.has_message(&mask, catalog.message_id("goodbye").unwrap()));
+        assert!(!catalog.is_loaded("es_MX"));
+
+        // only the locale that provides the message is loaded
+        let mut result = String::new();
+        let goodbye = catalog.message_id("goodbye").unwrap();
+        catalog.localize_into(&mut result, &locales, goodbye, &[])?;
+        assert_eq!(result, "Adiós.");
+        assert!(catalog.is_loaded("es_MX"));
+        assert!(!catalog.is_loaded("de_DE"));
+        assert!(!catalog.is_loaded("en_US"));
+
+        catalog.warm_up(&["en", "xy_ZW"]);
+        assert!(catalog.is_loaded("en_US"));
+        assert!(catalog.is_loaded("en_AU"));
+        assert!(!catalog.is_loaded("de_DE"));
+
+        let eager = Catalog::from_static(SOURCES, MESSAGES, &[], "en_US", false);
+        assert!(eager.is_loaded("de_DE"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn included() -> Result<()> {
+        let _ = pretty_env_logger::try_init();
+
+        const LIBRARY: ExportedSources = ExportedSources {
+            sources: &[
+                (
+                    "de_DE",
+                    &[("", LocaleSources::Plain(&["goodbye = Tschüss."]))],
+                ),
+                (
+                    "en_US",
+                    &[(
+                        "",
+                        LocaleSources::Plain(&["goodbye = Overridden.\nlibrary = Library"]),
+                    )],
+                ),
+                (
+                    "fr_FR",
+                    &[("", LocaleSources::Plain(&["library = Bibliothèque"]))],
+                ),
+            ],
+            messages: &[("goodbye", &[0b011]), ("library", &[0b110])],
+        };
+        const NEWER: ExportedSources = ExportedSources {
+            sources: &[(
+                "en_AU",
+                &[("", LocaleSources::Plain(&["library = Newer library"]))],
+            )],
+            messages: &[("library", &[0b1])],
+        };
+
+        let catalog = Catalog::from_static(SOURCES, MESSAGES, &[LIBRARY, NEWER], "en_US", true);
+
+        // locales only the library has are left out, and baked message IDs don't change
+        assert_eq!(catalog.locale_id("fr_FR"), None);
+        assert_eq!(catalog.messages.len(), 4);
+        assert_eq!(
+            catalog.message_id("greeting.title"),
+            Some(MessageId::new(2))
+        );
+        assert_eq!(catalog.message_id("library"), Some(MessageId::new(3)));
+        assert_eq!(&catalog.messages[0].1[..], &[0b1111]);
+        assert_eq!(&catalog.messages[3].1[..], &[0b0011]);
+
+        let localize = |locales: &[&str], message: &str| -> Result<String> {
+            let locales = catalog.locale_chain(locales, None);
+            let mut result = String::new();
+            let id = catalog.message_id(message).unwrap();
+            catalog.localize_into(&mut result, &locales, id, &[])?;
+            Ok(result)
+        };
+        assert_eq!(localize(&["de_DE"], "goodbye")?, "Tschüss.");
+        assert_eq!(localize(&["en_US"], "goodbye")?, "Goodbye.");
+        assert_eq!(localize(&["es_MX"], "library")?, "Library");
+        assert_eq!(localize(&["en_AU"], "library")?, "Newer library");
+
+        Ok(())
+    }
+
+    #[test]
+    fn included_shadowed() -> Result<()> {
+        let _ = pretty_env_logger::try_init();
+
+        const LIBRARY: ExportedSources = ExportedSources {
+            sources: &[(
+                "en_US",
+                &[(
+                    "",
+                    LocaleSources::Plain(&["greeting = Library\n    .title = Library title\n\
+                                            library = Library\n    .title = Library title"]),
+                )],
+            )],
+            messages: &[
+                ("greeting", &[0b1]),
+                ("greeting.title", &[0b1]),
+                ("library", &[0b1]),
+                ("library.title", &[0b1]),
+            ],
+        };
+
+        let catalog = Catalog::from_static(SOURCES, MESSAGES, &[LIBRARY], "en_US", true);
+
+        // our `greeting` wins, so en_US doesn't get the library's `greeting.title`
+        let mask =
+            |message: &str| &catalog.messages[catalog.message_id(message).unwrap().index()].1;
+        assert_eq!(&mask("greeting.title")[..], &[0b1000]);
+        assert_eq!(&mask("library.title")[..], &[0b0001]);
+
+        let locales = catalog.locale_chain(&["en_US", "de_DE"], None);
+        let mut result = String::new();
+        let title = catalog.message_id("greeting.title").unwrap();
+        catalog.localize_into(&mut result, &locales, title, &[])?;
+        assert_eq!(result, "Begrüßung");
+
+        let namespaces = ["auth"].iter().copied().collect();
+        assert_eq!(message_path(&namespaces, "auth.login.title"), "auth.login");
+        assert_eq!(message_path(&namespaces, "authors.title"), "authors");
+        assert_eq!(message_path(&namespaces, "greeting"), "greeting");
+
+        Ok(())
+    }
+
+    #[test]
+    fn namespace_clashes() {
+        let namespaces = ["auth", "billing"].iter().copied().collect();
+        let paths = vec![
+            "auth",
+            "auth.title",
+            "auth.login",
+            "billing.plan",
+            "billings",
+        ];
+        let clashes = super::namespace_clashes(&namespaces, paths.into_iter());
+        assert_eq!(clashes.into_iter().collect::<Vec<_>>(), vec!["auth"]);
+    }
+}
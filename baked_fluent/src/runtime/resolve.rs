@@ -0,0 +1,505 @@
+//! Message resolution.
+//!
+//! This follows the semantics of `fluent_bundle::resolve`, with two differences:
+//! - arguments are looked up in the caller's slice by reference, instead of being cloned into a
+//!   `HashMap` on every call.
+//! - output is streamed into the caller's writer as it's resolved, instead of being built up in
+//!   intermediate `String`s. Because of this, a cyclic reference only replaces the placeable
+//!   it's in with `___`, rather than the whole message.
+//!
+//! Formatting a message that only uses variables, literals, selectors and references to other
+//! messages and terms doesn't allocate.
+
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
+use fluent_bundle::resolve::ResolverError;
+use fluent_bundle::FluentValue;
+use fluent_syntax::ast;
+use fluent_syntax::unicode::unescape_unicode;
+use intl_pluralrules::PluralCategory;
+
+use super::bundle::Bundle;
+
+/// How deeply messages and terms can reference each other before tracking them allocates.
+/// Deeper chains still resolve, as long as they aren't cycles.
+const INLINE_DEPTH: usize = 32;
+
+/// How deeply messages and terms can reference each other at all, so that resolving can't
+/// overflow the stack. A reference past this is written as a placeholder, like one that would
+/// close a cycle.
+const MAX_DEPTH: usize = 256;
+
+/// Write a pattern found with `Bundle::locate` into `writer`.
+///
+/// Returns any errors encountered; parts of the message that failed to resolve are written as
+/// `___`. A message without a value is written as its `path`.
+pub fn format_pattern_into<W: Write>(
+    writer: &mut W,
+    bundle: &Bundle,
+    path: &str,
+    pattern: Option<&ast::Pattern>,
+    args: &[(&str, &FluentValue)],
+) -> Result<Vec<ResolverError>, fmt::Error> {
+    let mut scope = Scope {
+        bundle,
+        args: Args::Caller(args),
+        travelled: [std::ptr::null(); INLINE_DEPTH],
+        deeper: Vec::new(),
+        depth: 0,
+        errors: Vec::new(),
+    };
+    match pattern {
+        Some(pattern) => scope.track(writer, pattern)?,
+        None => {
+            // like fluent-bundle, fall back to the message's ID
+            scope.errors.push(ResolverError::None);
+            writer.write_str(path)?;
+        }
+    }
+    Ok(scope.errors)
+}
+
+/// A resolved value; borrowed from the arguments or the source wherever possible.
+#[derive(Debug, Clone, PartialEq)]
+enum Value<'a> {
+    String(Cow<'a, str>),
+    Number(Cow<'a, str>),
+}
+
+impl<'a> Value<'a> {
+    fn from_fluent(value: &'a FluentValue) -> Value<'a> {
+        match value {
+            FluentValue::String(s) => Value::String(Cow::Borrowed(s)),
+            FluentValue::Number(n) => Value::Number(Cow::Borrowed(n)),
+        }
+    }
+
+    fn number(literal: &'a str) -> Result<Value<'a>, ResolverError> {
+        literal
+            .parse::<f64>()
+            .map(|_| Value::Number(Cow::Borrowed(literal)))
+            .map_err(|_| ResolverError::Value)
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Value::String(s) | Value::Number(s) => s,
+        }
+    }
+
+    /// Whether a variant key matches a selector; see `FluentValue::matches`.
+    fn matches(&self, bundle: &Bundle, selector: &Value) -> bool {
+        match (self, selector) {
+            (Value::String(a), Value::String(b)) | (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(category), Value::Number(n)) => {
+                let category = match &category[..] {
+                    "zero" => PluralCategory::ZERO,
+                    "one" => PluralCategory::ONE,
+                    "two" => PluralCategory::TWO,
+                    "few" => PluralCategory::FEW,
+                    "many" => PluralCategory::MANY,
+                    "other" => PluralCategory::OTHER,
+                    _ => return false,
+                };
+                bundle.plural_rules.select(&n[..]) == Ok(category)
+            }
+            (Value::Number(..), Value::String(..)) => false,
+        }
+    }
+}
+
+/// Arguments visible to the pattern being resolved.
+enum Args<'a> {
+    /// Passed in by the caller of `format_pattern_into`.
+    Caller(&'a [(&'a str, &'a FluentValue)]),
+    /// Passed to a term, e.g. `{ -brand(case: "nominative") }`.
+    Term(Vec<(&'a str, Value<'a>)>),
+}
+
+impl<'a> Args<'a> {
+    fn get(&self, name: &str) -> Option<Value<'a>> {
+        // argument lists are short; a linear scan beats hashing.
+        match self {
+            Args::Caller(args) => args
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| Value::from_fluent(value)),
+            Args::Term(args) => args
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.clone()),
+        }
+    }
+}
+
+/// Why an expression couldn't be written.
+enum Error {
+    /// The expression couldn't be resolved; nothing was written.
+    Resolver(ResolverError),
+    /// The writer failed.
+    Fmt(fmt::Error),
+}
+
+impl From<ResolverError> for Error {
+    fn from(err: ResolverError) -> Self {
+        Error::Resolver(err)
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Error::Fmt(err)
+    }
+}
+
+/// State for a single `format_pattern_into` call.
+struct Scope<'a> {
+    bundle: &'a Bundle,
+    args: Args<'a>,
+    /// Patterns currently being resolved, to detect cycles.
+    /// The first `INLINE_DEPTH` are stored inline to avoid allocating.
+    travelled: [*const ast::Pattern<'a>; INLINE_DEPTH],
+    /// The rest, for unusually deep chains of references.
+    deeper: Vec<*const ast::Pattern<'a>>,
+    depth: usize,
+    /// Errors that didn't prevent resolution.
+    errors: Vec<ResolverError>,
+}
+
+impl<'a> Scope<'a> {
+    /// Write a message, term or attribute value, failing on cycles and on chains of references
+    /// deeper than `MAX_DEPTH`.
+    fn track<W: Write>(
+        &mut self,
+        writer: &mut W,
+        pattern: &'a ast::Pattern<'a>,
+    ) -> Result<(), fmt::Error> {
+        let ptr = pattern as *const _;
+        let inline = self.depth.min(INLINE_DEPTH);
+        if self.depth >= MAX_DEPTH
+            || self.travelled[..inline].contains(&ptr)
+            || self.deeper.contains(&ptr)
+        {
+            self.errors.push(ResolverError::Cyclic);
+            return writer.write_str("___");
+        }
+        if self.depth < INLINE_DEPTH {
+            self.travelled[self.depth] = ptr;
+        } else {
+            self.deeper.push(ptr);
+        }
+        self.depth += 1;
+        let result = self.pattern(writer, pattern);
+        self.depth -= 1;
+        if self.depth >= INLINE_DEPTH {
+            self.deeper.pop();
+        }
+        result
+    }
+
+    fn pattern<W: Write>(
+        &mut self,
+        writer: &mut W,
+        pattern: &'a ast::Pattern<'a>,
+    ) -> Result<(), fmt::Error> {
+        for element in &pattern.elements {
+            match element {
+                ast::PatternElement::TextElement(text) => writer.write_str(text)?,
+                ast::PatternElement::Placeable(expression) => {
+                    match self.expression(writer, expression) {
+                        Ok(()) => (),
+                        Err(Error::Resolver(err)) => {
+                            self.errors.push(err);
+                            writer.write_str("___")?;
+                        }
+                        Err(Error::Fmt(err)) => return Err(err),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expression<W: Write>(
+        &mut self,
+        writer: &mut W,
+        expression: &'a ast::Expression<'a>,
+    ) -> Result<(), Error> {
+        match expression {
+            ast::Expression::InlineExpression(expression) => {
+                self.inline_expression(writer, expression)
+            }
+            ast::Expression::SelectExpression { selector, variants } => {
+                if let Ok(selector) = self.value(selector) {
+                    for variant in variants {
+                        let key = match variant.key {
+                            ast::VariantKey::Identifier { name } => {
+                                Value::String(Cow::Borrowed(name))
+                            }
+                            ast::VariantKey::NumberLiteral { value } => Value::number(value)?,
+                        };
+                        if key.matches(self.bundle, &selector) {
+                            return Ok(self.pattern(writer, &variant.value)?);
+                        }
+                    }
+                }
+
+                let default = variants
+                    .iter()
+                    .find(|variant| variant.default)
+                    .ok_or(ResolverError::None)?;
+                Ok(self.pattern(writer, &default.value)?)
+            }
+        }
+    }
+
+    fn inline_expression<W: Write>(
+        &mut self,
+        writer: &mut W,
+        expression: &'a ast::InlineExpression<'a>,
+    ) -> Result<(), Error> {
+        match expression {
+            ast::InlineExpression::MessageReference { id, attribute } => {
+                let message = self.bundle.message(id.name).ok_or(ResolverError::None)?;
+                let pattern = match attribute {
+                    Some(attribute) => message
+                        .attributes
+                        .iter()
+                        .find(|attr| attr.id.name == attribute.name)
+                        .map(|attr| &attr.value),
+                    None => message.value.as_ref(),
+                };
+                Ok(self.track(writer, pattern.ok_or(ResolverError::None)?)?)
+            }
+            ast::InlineExpression::TermReference {
+                id,
+                attribute,
+                arguments,
+            } => {
+                let term = self.bundle.term(id.name).ok_or(ResolverError::None)?;
+                let pattern = match attribute {
+                    Some(attribute) => term
+                        .attributes
+                        .iter()
+                        .find(|attr| attr.id.name == attribute.name)
+                        .map(|attr| &attr.value)
+                        .ok_or(ResolverError::None)?,
+                    None => &term.value,
+                };
+
+                // terms only see the arguments they're explicitly passed
+                let mut term_args = Vec::new();
+                if let Some(arguments) = arguments {
+                    for arg in &arguments.named {
+                        if let Ok(value) = self.value(&arg.value) {
+                            term_args.push((arg.name.name, value));
+                        }
+                    }
+                }
+                let caller_args = std::mem::replace(&mut self.args, Args::Term(term_args));
+                let result = self.track(writer, pattern);
+                self.args = caller_args;
+                Ok(result?)
+            }
+            ast::InlineExpression::Placeable { expression } => self.expression(writer, expression),
+            expression => Ok(writer.write_str(self.value(expression)?.as_str())?),
+        }
+    }
+
+    /// Resolve an expression to a value, e.g. for use as a selector.
+    /// Only allocates for string literals with escapes and message or term references.
+    fn value(
+        &mut self,
+        expression: &'a ast::InlineExpression<'a>,
+    ) -> Result<Value<'a>, ResolverError> {
+        match expression {
+            ast::InlineExpression::StringLiteral { value } => {
+                Ok(Value::String(unescape_unicode(value)))
+            }
+            ast::InlineExpression::NumberLiteral { value } => {
+                Value::number(value).map_err(|_| ResolverError::None)
+            }
+            // no functions are registered
+            ast::InlineExpression::FunctionReference { .. } => Err(ResolverError::None),
+            ast::InlineExpression::VariableReference { id } => {
+                self.args.get(id.name).ok_or(ResolverError::None)
+            }
+            expression => {
+                let mut result = String::new();
+                match self.inline_expression(&mut result, expression) {
+                    Ok(()) => Ok(Value::String(Cow::Owned(result))),
+                    Err(Error::Resolver(err)) => Err(err),
+                    Err(Error::Fmt(_)) => unreachable!("writing to a String can't fail"),
+                }
+            }
+        }
+    }
+}
+
+/// Adapts an `io::Write` to `fmt::Write`, holding on to the first io error.
+pub struct IoWriter<'a, W: std::io::Write> {
+    writer: &'a mut W,
+    error: Option<std::io::Error>,
+}
+
+impl<'a, W: std::io::Write> IoWriter<'a, W> {
+    pub fn new(writer: &'a mut W) -> IoWriter<'a, W> {
+        IoWriter {
+            writer,
+            error: None,
+        }
+    }
+
+    /// The io error that caused the last `fmt::Error`, if any.
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+}
+
+impl<'a, W: std::io::Write> Write for IoWriter<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluent_bundle::{FluentBundle, FluentResource};
+
+    fn format(bundle: &Bundle, path: &str, args: &[(&str, &FluentValue)]) -> Option<String> {
+        let mut result = String::new();
+        let pattern = bundle.pattern(bundle.locate(path)?);
+        format_pattern_into(&mut result, bundle, path, pattern, args).unwrap();
+        Some(result)
+    }
+
+    #[test]
+    fn matches_fluent_bundle() {
+        let source = r#"
+-brand = { $case ->
+   *[nominative] Firefox
+    [genitive] Firefoxa
+}
+    .gender = masculine
+plain = Just text.
+args = { $name } has { $count } { $count ->
+    [one] thing
+    [2] pair
+   *[other] things
+}
+term = About { -brand(case: "genitive") } for { $name }.
+select-term = { -brand.gender ->
+    [masculine] him
+   *[other] them
+}
+reference = { plain } { args.title }
+    .title = Title for { $name }
+missing = { $nope } and { other-nope } and { FUNC() }
+cycle-a = { cycle-b }
+cycle-b = x { cycle-a } y
+unicode = { "é" }
+no-value =
+    .attr = Only an attribute.
+"#;
+        let resource = FluentResource::try_new(source.to_string()).unwrap();
+        let mut fluent_bundle = FluentBundle::new(&["en-US"]);
+        fluent_bundle.add_resource(&resource).unwrap();
+        let bundle = Bundle::new(
+            "en-US",
+            vec![FluentResource::try_new(source.to_string()).unwrap()],
+        );
+
+        let name = FluentValue::from("Jamie");
+        for count in &[0, 1, 2, 5] {
+            let count = FluentValue::from(*count as isize);
+            let args = [("name", &name), ("count", &count)];
+            let fluent_args = args.iter().map(|(k, v)| (*k, (*v).clone())).collect();
+
+            for path in &[
+                "plain",
+                "args",
+                "term",
+                "select-term",
+                "reference",
+                "reference.title",
+                "missing",
+                "unicode",
+                "no-value",
+                "no-value.attr",
+                "no-value.nope",
+                "nope",
+            ] {
+                assert_eq!(
+                    format(&bundle, path, &args),
+                    fluent_bundle
+                        .format(path, Some(&fluent_args))
+                        .map(|(result, _)| result),
+                    "formatting {:?} with {:?}",
+                    path,
+                    args
+                );
+            }
+        }
+
+        // cycles only break the placeable they're in
+        assert_eq!(format(&bundle, "cycle-a", &[]).unwrap(), "x ___ y");
+    }
+
+    #[test]
+    fn deep_references() {
+        // a chain of references deeper than INLINE_DEPTH that isn't a cycle, and one that ends
+        // in a cycle past INLINE_DEPTH
+        let depth = INLINE_DEPTH + 1;
+        let mut source = String::new();
+        for i in 0..depth {
+            source.push_str(&format!("m{} = {{ m{} }}\n", i, i + 1));
+            source.push_str(&format!("c{} = {{ c{} }}\n", i, i + 1));
+        }
+        source.push_str(&format!("m{} = end\n", depth));
+        source.push_str(&format!("c{} = x {{ c{} }}\n", depth, depth - 1));
+        let bundle = Bundle::new("en-US", vec![FluentResource::try_new(source).unwrap()]);
+
+        assert_eq!(format(&bundle, "m0", &[]).unwrap(), "end");
+        assert_eq!(format(&bundle, "c0", &[]).unwrap(), "x ___");
+
+        // chains can only be MAX_DEPTH references deep, however long they are
+        let mut source = String::new();
+        for i in 0..MAX_DEPTH {
+            source.push_str(&format!("m{} = {{ m{} }}\n", i, i + 1));
+        }
+        source.push_str(&format!("m{} = end\n", MAX_DEPTH));
+        let bundle = Bundle::new("en-US", vec![FluentResource::try_new(source).unwrap()]);
+        assert_eq!(format(&bundle, "m1", &[]).unwrap(), "end");
+        assert_eq!(format(&bundle, "m0", &[]).unwrap(), "___");
+    }
+
+    #[test]
+    fn io_writer() {
+        struct Full;
+        impl std::io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WriteZero.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut bytes = Vec::new();
+        write!(IoWriter::new(&mut bytes), "héllo {}", 1).unwrap();
+        assert_eq!(bytes, "héllo 1".as_bytes());
+
+        let mut full = Full;
+        let mut writer = IoWriter::new(&mut full);
+        assert!(writer.write_str("hello").is_err());
+        assert_eq!(
+            writer.take_error().unwrap().kind(),
+            std::io::ErrorKind::WriteZero
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Checks that creating a localizer and localizing into a writer don't allocate, once warmed up.
+//! (This is its own test binary since it replaces the global allocator.)
+
+use baked_fluent::runtime::I18nValue;
+use baked_fluent::{impl_localize, Localize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+impl_localize! {
+    #[path("tests/i18n")]
+    #[default_locale("en_US")]
+    struct TestLocalizer(_);
+}
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn localize_into_doesnt_allocate() {
+    let name = I18nValue::from("Jamie");
+    let friends = I18nValue::from(5);
+    let args = [("name", &name), ("friends", &friends)];
+    let header = Some("es-MX, en;q=0.5");
+    let mut result = String::with_capacity(256);
+
+    // initialize the baked translations and cache the locale chain
+    TestLocalizer::new(&[], header)
+        .localize_into(&mut result, "greeting", &args)
+        .unwrap();
+    result.clear();
+
+    let allocations = count_allocations(|| {
+        let loc = TestLocalizer::new(&[], header);
+        loc.localize_into(&mut result, "greeting", &args).unwrap();
+        loc.localize_into(&mut result, "title", &[]).unwrap();
+    });
+    assert_eq!(result, "¡Hola, Jamie! Tienes 5 amigos.Red Social Ambigua 1");
+    assert_eq!(allocations, 0);
+}
@@ -22,3 +22,49 @@ fn init() {
         "Hello Jamie! You have 0 friends."
     );
 }
+
+#[test]
+fn copy() {
+    fn assert_copy<T: Copy + Send + Sync + 'static>(_: T) {}
+
+    let loc = TestLocalizer::new(&["es_MX"], None);
+    assert_copy(loc);
+
+    let copied = loc;
+    assert_eq!(copied.locale_chain(), &["es_MX", "en_US"]);
+    assert_eq!(loc.locale_chain(), copied.locale_chain());
+    assert_eq!(
+        localize!(copied, greeting, name = "Jamie", friends = 1).unwrap(),
+        "¡Hola, Jamie! ¡Tienes un amigo!"
+    );
+}
+
+#[test]
+fn message_ids() {
+    use baked_fluent::runtime::I18nValue;
+
+    let loc = TestLocalizer::new(&["es_MX"], None);
+
+    assert_eq!(
+        TestLocalizer::message_id("greeting"),
+        Some(TestLocalizer::GREETING)
+    );
+    assert_eq!(
+        TestLocalizer::message_id("title"),
+        Some(TestLocalizer::TITLE)
+    );
+    assert_eq!(TestLocalizer::message_id("bananas"), None);
+    assert!(loc.has_message_id(TestLocalizer::TITLE));
+
+    let name = I18nValue::from("Jamie");
+    let friends = I18nValue::from(1);
+    assert_eq!(
+        loc.localize_id(
+            TestLocalizer::GREETING,
+            &[("name", &name), ("friends", &friends)]
+        )
+        .unwrap(),
+        localize!(loc, greeting, name = "Jamie", friends = 1).unwrap()
+    );
+    assert!(loc.localize("bananas", &[]).is_err());
+}
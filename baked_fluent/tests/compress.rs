@@ -0,0 +1,43 @@
+//! Run with `--features with-zstd,with-deflate`.
+#![cfg(all(feature = "with-zstd", feature = "with-deflate"))]
+
+use baked_fluent::{impl_localize, localize, Localize};
+
+impl_localize! {
+    #[path("tests/i18n")]
+    #[default_locale("en_US")]
+    #[lazy(true)]
+    #[compress("zstd")]
+    struct ZstdLocalizer(_);
+}
+
+// each localizer needs its own module
+mod deflate {
+    baked_fluent::impl_localize! {
+        #[path("tests/i18n")]
+        #[default_locale("en_US")]
+        #[compress("deflate")]
+        pub struct DeflateLocalizer(_);
+    }
+}
+use deflate::DeflateLocalizer;
+
+#[test]
+fn zstd() {
+    ZstdLocalizer::warm_up(&["en"]);
+    let loc = ZstdLocalizer::new(&["es_MX"], None);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie", friends = 1).unwrap(),
+        "¡Hola, Jamie! ¡Tienes un amigo!"
+    );
+    assert_eq!(localize!(loc, title).unwrap(), "Red Social Ambigua 1");
+}
+
+#[test]
+fn deflate() {
+    let loc = DeflateLocalizer::new(&[], None);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie", friends = 2).unwrap(),
+        "Hello Jamie! You have 2 friends."
+    );
+}
@@ -0,0 +1,48 @@
+use baked_fluent::{impl_localize, localize, Localize};
+
+/// Stands in for a library crate shipping its own translations.
+mod library {
+    use baked_fluent::impl_localize;
+
+    impl_localize! {
+        #[path("tests/namespaces/shared")]
+        #[path("tests/namespaces/auth", namespace = "auth")]
+        #[default_locale("en_US")]
+        #[export_sources(I18N)]
+        pub struct LibraryLocalizer(_);
+    }
+}
+
+impl_localize! {
+    #[path("tests/i18n")]
+    #[default_locale("en_US")]
+    #[include_sources(library::I18N)]
+    struct AppLocalizer(_);
+}
+
+#[test]
+fn included_sources() {
+    let _ = pretty_env_logger::try_init();
+
+    let loc = AppLocalizer::new(&[], Some("es-MX"));
+    assert_eq!(
+        localize!(loc, farewell).unwrap(),
+        "¡Adiós desde Baked Fluent!"
+    );
+    assert_eq!(
+        localize!(loc, auth.login).unwrap(),
+        "Iniciar sesión en el servicio de autenticación"
+    );
+    assert!(loc.has_message("auth.login.title"));
+    assert!(AppLocalizer::message_id("farewell").is_some());
+
+    // the app's own translations take precedence
+    let loc = AppLocalizer::new(&[], None);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie", friends = 1).unwrap(),
+        "Hello Jamie! You have a friend!"
+    );
+    // and they decide which locales are available
+    let loc = AppLocalizer::new(&["fr_FR"], None);
+    assert_eq!(loc.locale_chain(), &["en_US"]);
+}
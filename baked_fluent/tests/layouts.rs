@@ -0,0 +1,44 @@
+use baked_fluent::{impl_localize, localize, Localize};
+
+impl_localize! {
+    #[path("tests/layouts/flat")]
+    #[default_locale("en-US")]
+    #[layout("flat")]
+    struct FlatLocalizer(_);
+}
+
+mod suffix {
+    use baked_fluent::impl_localize;
+
+    impl_localize! {
+        #[path("tests/layouts/suffix")]
+        #[default_locale("en-US")]
+        #[layout("suffix")]
+        pub struct SuffixLocalizer(_);
+    }
+}
+use suffix::SuffixLocalizer;
+
+#[test]
+fn flat() {
+    let _ = pretty_env_logger::try_init();
+
+    let loc = FlatLocalizer::new(&[], Some("es-MX"));
+    assert_eq!(loc.locale_chain(), &["es-MX", "en-US"]);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie").unwrap(),
+        "¡Hola, Jamie!"
+    );
+}
+
+#[test]
+fn suffix() {
+    let _ = pretty_env_logger::try_init();
+
+    let loc = SuffixLocalizer::new(&["es_MX"], None);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie").unwrap(),
+        "¡Hola, Jamie!"
+    );
+    assert_eq!(localize!(loc, settings).unwrap(), "Settings");
+}
@@ -0,0 +1 @@
+greeting = ¡Hola, { $name }!
@@ -0,0 +1 @@
+settings = Settings
@@ -0,0 +1,27 @@
+use baked_fluent::{impl_localize, localize, Localize};
+
+impl_localize! {
+    #[path("tests/i18n")]
+    #[default_locale("en_US")]
+    #[lazy(true)]
+    struct LazyLocalizer(_);
+}
+
+#[test]
+fn lazy() {
+    let _ = pretty_env_logger::try_init();
+
+    LazyLocalizer::warm_up(&["en_US"]);
+
+    let loc = LazyLocalizer::new(&[], Some("es-MX"));
+    assert!(loc.has_message("greeting"));
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie", friends = 1).unwrap(),
+        "¡Hola, Jamie! ¡Tienes un amigo!"
+    );
+    let loc = LazyLocalizer::new(&[], None);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie", friends = 1).unwrap(),
+        "Hello Jamie! You have a friend!"
+    );
+}
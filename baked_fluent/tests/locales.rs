@@ -0,0 +1,25 @@
+use baked_fluent::{impl_localize, localize, Localize};
+
+impl_localize! {
+    #[path("tests/i18n")]
+    #[default_locale("en_US")]
+    #[locales(exclude = ["es_MX"])]
+    struct EnglishLocalizer(_);
+}
+
+#[test]
+fn excluded_locales_are_left_out() {
+    let _ = pretty_env_logger::try_init();
+
+    // unless the build overrides the choice of locales
+    if option_env!("BAKED_FLUENT_LOCALES").is_some() {
+        return;
+    }
+
+    let loc = EnglishLocalizer::new(&["es_MX"], Some("es-MX"));
+    assert_eq!(loc.locale_chain(), &["en_US"]);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie", friends = 1).unwrap(),
+        "Hello Jamie! You have a friend!"
+    );
+}
@@ -0,0 +1,85 @@
+use baked_fluent::{impl_localize, localize, Localize};
+
+impl_localize! {
+    #[path("tests/namespaces/shared")]
+    #[path("tests/i18n")]
+    #[path("tests/namespaces/auth", namespace = "auth")]
+    #[default_locale("en_US")]
+    struct MergedLocalizer(_);
+}
+
+mod strict {
+    use baked_fluent::impl_localize;
+
+    // these paths don't conflict
+    impl_localize! {
+        #[path("tests/i18n")]
+        #[path("tests/namespaces/auth", namespace = "auth")]
+        #[default_locale("en_US")]
+        #[on_conflict("error")]
+        pub struct StrictLocalizer(_);
+    }
+}
+
+#[test]
+fn later_paths_override_earlier_ones() {
+    let _ = pretty_env_logger::try_init();
+
+    let loc = MergedLocalizer::new(&[], None);
+    assert_eq!(
+        localize!(loc, greeting, name = "Jamie", friends = 1).unwrap(),
+        "Hello Jamie! You have a friend!"
+    );
+    assert_eq!(
+        localize!(loc, farewell).unwrap(),
+        "Goodbye from Baked Fluent!"
+    );
+}
+
+#[test]
+fn namespaces() {
+    let _ = pretty_env_logger::try_init();
+
+    let loc = MergedLocalizer::new(&[], Some("es-MX"));
+    // terms are scoped to their namespace
+    assert_eq!(
+        localize!(loc, auth.login).unwrap(),
+        "Iniciar sesión en el servicio de autenticación"
+    );
+    assert_eq!(
+        localize!(loc, farewell).unwrap(),
+        "¡Adiós desde Baked Fluent!"
+    );
+    assert_eq!(localize!(loc, auth.login.title).unwrap(), "Login");
+    assert!(!loc.has_message("login"));
+    assert!(!loc.has_message("auth"));
+    assert_eq!(
+        loc.localize_id(MergedLocalizer::AUTH__LOGIN, &[]).unwrap(),
+        "Iniciar sesión en el servicio de autenticación"
+    );
+
+    let loc = strict::StrictLocalizer::new(&[], None);
+    assert_eq!(
+        localize!(loc, auth.login).unwrap(),
+        "Log in to the auth service"
+    );
+}
+
+#[test]
+fn coverage() {
+    let coverage = MergedLocalizer::translation_coverage();
+    let locales = coverage
+        .iter()
+        .map(|coverage| (coverage.locale, coverage.translated(), coverage.total))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        locales,
+        vec![("en_US", 5, 5), ("es_MX", 4, 5), ("fr_FR", 1, 5)]
+    );
+    assert_eq!(coverage[1].missing, &["auth.login.title"]);
+    assert_eq!(
+        coverage[2].missing,
+        &["auth.login", "auth.login.title", "greeting", "title"]
+    );
+    assert_eq!(coverage[2].percent(), 20.0);
+}
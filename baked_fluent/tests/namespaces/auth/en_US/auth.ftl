@@ -0,0 +1,3 @@
+login = Log in to { -brand }
+    .title = Login
+-brand = the auth service
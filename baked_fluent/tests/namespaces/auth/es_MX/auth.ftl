@@ -0,0 +1,2 @@
+login = Iniciar sesión en { -brand }
+-brand = el servicio de autenticación
@@ -0,0 +1,4 @@
+# translations shared between services
+greeting = Overridden by tests/i18n.
+farewell = Goodbye from { -brand }!
+-brand = Baked Fluent
@@ -0,0 +1,2 @@
+farewell = ¡Adiós desde { -brand }!
+-brand = Baked Fluent
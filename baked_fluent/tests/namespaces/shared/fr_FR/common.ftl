@@ -0,0 +1,2 @@
+farewell = Au revoir de { -brand } !
+-brand = Baked Fluent
@@ -0,0 +1,26 @@
+use baked_fluent::{impl_localize, localize, Localize};
+
+impl_localize! {
+    #[path("tests/nested")]
+    #[default_locale("en_US")]
+    #[files(exclude = ["drafts/**"])]
+    struct NestedLocalizer(_);
+}
+
+#[test]
+fn nested_files() {
+    let _ = pretty_env_logger::try_init();
+
+    let loc = NestedLocalizer::new(&[], None);
+    assert_eq!(localize!(loc, title).unwrap(), "Settings");
+    assert_eq!(
+        localize!(loc, privacy.title).unwrap(),
+        "Who can see your profile"
+    );
+    assert_eq!(
+        loc.localize_id(NestedLocalizer::DELETE_ACCOUNT, &[])
+            .unwrap(),
+        "Delete account"
+    );
+    assert!(!loc.has_message("unfinished"));
+}
@@ -0,0 +1 @@
+unfinished = Work in progress
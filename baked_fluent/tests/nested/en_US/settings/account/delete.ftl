@@ -0,0 +1 @@
+delete-account = Delete account
@@ -0,0 +1,2 @@
+privacy = Privacy
+    .title = Who can see your profile
@@ -0,0 +1,79 @@
+//! Localizes from many threads at once, to check that the shared catalog is safe to use from
+//! multi-threaded servers.
+
+use baked_fluent::{impl_localize, localize, Localize};
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+impl_localize! {
+    #[path("tests/i18n")]
+    #[default_locale("en_US")]
+    struct TestLocalizer(_);
+}
+
+const THREADS: usize = 16;
+const ITERATIONS: usize = 2000;
+
+/// (accept-language header, friends, expected output)
+const CASES: &[(Option<&str>, isize, &str)] = &[
+    (None, 1, "Hello Jamie! You have a friend!"),
+    (Some("es-MX"), 1, "¡Hola, Jamie! ¡Tienes un amigo!"),
+    (Some("es-MX"), 5, "¡Hola, Jamie! Tienes 5 amigos."),
+    (Some("de, en;q=0.5"), 5, "Hello Jamie! You have 5 friends."),
+    (Some("es;q=0.1, en"), 5, "Hello Jamie! You have 5 friends."),
+];
+
+#[test]
+fn localize_from_many_threads() {
+    let _ = pretty_env_logger::try_init();
+
+    // start everything at once, so that the catalog is initialized under contention too
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let threads = (0..THREADS)
+        .map(|thread| {
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                let mut result = String::new();
+                for i in 0..ITERATIONS {
+                    let (header, friends, expected) = CASES[(thread + i) % CASES.len()];
+                    // unique headers push entries out of the chain cache while others read it
+                    let unique = format!("x-{}-{};q=0.1", thread, i);
+                    let header = if i % 7 == 0 {
+                        header.map(|header| format!("{}, {}", header, unique))
+                    } else {
+                        header.map(String::from)
+                    };
+
+                    let loc = TestLocalizer::new(&[], header.as_ref().map(|h| &h[..]));
+                    assert_eq!(
+                        localize!(loc, greeting, name = "Jamie", friends = friends).unwrap(),
+                        expected
+                    );
+
+                    result.clear();
+                    loc.localize_id_into(&mut result, TestLocalizer::TITLE, &[])
+                        .unwrap();
+                    assert!(!result.is_empty());
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().expect("localizing thread panicked");
+    }
+}
+
+#[test]
+fn localizers_can_be_sent() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let loc = TestLocalizer::new(&["es_MX"], None);
+    assert_send_sync(&loc);
+    let result = thread::spawn(move || localize!(loc, greeting, name = "Jamie", friends = 2))
+        .join()
+        .unwrap()
+        .unwrap();
+    assert_eq!(result, "¡Hola, Jamie! Tienes 2 amigos.");
+}
@@ -0,0 +1,9 @@
+use baked_fluent::impl_localize;
+
+impl_localize! {
+    #[path("../../../baked_fluent/tests/ui/i18n-invalid-utf8")]
+    #[default_locale("en_US")]
+    struct TestLocalizer(_);
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+// `en_US/hello.ftl` is a symlink to a file that doesn't exist, so it's found but can't be
+// read, as if it had been deleted during the build.
+use baked_fluent::impl_localize;
+
+impl_localize! {
+    #[path("../../../baked_fluent/tests/ui/i18n-vanished")]
+    #[default_locale("en_US")]
+    struct TestLocalizer(_);
+}
+
+fn main() {}
@@ -0,0 +1 @@
+missing.ftl
\ No newline at end of file
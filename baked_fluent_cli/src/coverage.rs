@@ -0,0 +1,115 @@
+//! Coverage reports, as text, JSON or a Markdown table.
+
+use baked_fluent_sources::coverage::Coverage;
+use serde_json::{json, Value};
+
+/// A percentage with one decimal, rounded down, so that only complete locales show 100%.
+fn percent(coverage: &Coverage) -> String {
+    format!("{:.1}%", (coverage.percent() * 10.0).floor() / 10.0)
+}
+
+/// One locale per line, followed by the IDs it's missing.
+pub fn to_text(coverage: &[Coverage]) -> String {
+    let width = coverage
+        .iter()
+        .map(|coverage| coverage.locale.len())
+        .max()
+        .unwrap_or(0);
+    let mut text = String::new();
+    for coverage in coverage {
+        text.push_str(&format!(
+            "{:width$}  {:>6}  {}/{}\n",
+            coverage.locale,
+            percent(coverage),
+            coverage.translated(),
+            coverage.total,
+            width = width
+        ));
+        for path in &coverage.missing {
+            text.push_str(&format!("    missing {}\n", path));
+        }
+    }
+    text
+}
+
+pub fn to_json(coverage: &[Coverage]) -> Value {
+    let locales = coverage
+        .iter()
+        .map(|coverage| {
+            json!({
+                "locale": coverage.locale,
+                "translated": coverage.translated(),
+                "total": coverage.total,
+                "percent": coverage.percent(),
+                "missing": coverage.missing,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "locales": locales })
+}
+
+pub fn to_markdown(coverage: &[Coverage]) -> String {
+    let mut markdown = String::from(
+        "| Locale | Translated | Coverage | Missing |\n\
+         |--------|-----------:|---------:|---------|\n",
+    );
+    for coverage in coverage {
+        let missing = coverage
+            .missing
+            .iter()
+            .map(|path| format!("`{}`", path))
+            .collect::<Vec<_>>();
+        markdown.push_str(&format!(
+            "| {} | {}/{} | {} | {} |\n",
+            coverage.locale,
+            coverage.translated(),
+            coverage.total,
+            percent(coverage),
+            missing.join(", ")
+        ));
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let coverage = vec![
+            Coverage {
+                locale: "en_US".into(),
+                total: 3,
+                missing: vec![],
+            },
+            Coverage {
+                locale: "zh_Hant".into(),
+                total: 3,
+                missing: vec!["b".into(), "c.title".into()],
+            },
+        ];
+        assert_eq!(
+            to_text(&coverage),
+            "en_US    100.0%  3/3\n\
+             zh_Hant   33.3%  1/3\n    missing b\n    missing c.title\n"
+        );
+        assert_eq!(
+            to_markdown(&coverage),
+            "| Locale | Translated | Coverage | Missing |\n\
+             |--------|-----------:|---------:|---------|\n\
+             | en_US | 3/3 | 100.0% |  |\n\
+             | zh_Hant | 1/3 | 33.3% | `b`, `c.title` |\n"
+        );
+        assert_eq!(
+            to_json(&coverage)["locales"][1],
+            json!({
+                "locale": "zh_Hant",
+                "translated": 1,
+                "total": 3,
+                "percent": 100.0 / 3.0,
+                "missing": ["b", "c.title"],
+            })
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! `baked-fluent`: checks translation trees the way `impl_localize!` does, without compiling
+//! anything, for translators and CI.
+//!
+//! ```sh
+//! $ baked-fluent check i18n/ --default en_US
+//! $ baked-fluent check i18n/ auth=i18n-auth/ --default en_US --format json
+//! $ baked-fluent usage i18n/ --default en_US --src src/
+//! $ baked-fluent coverage i18n/ --default en_US --format markdown
+//! $ baked-fluent new-locale fr_FR i18n/ --default en_US
+//! $ baked-fluent update i18n/ --default en_US
+//! ```
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::path::PathBuf;
+use std::process;
+
+mod coverage;
+mod report;
+mod skeleton;
+mod tree;
+mod usage;
+
+use report::Report;
+use skeleton::{Change, Stub};
+use tree::Options;
+
+fn main() {
+    let matches = App::new("baked-fluent")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Checks fluent translation trees for baked_fluent")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("check")
+                .about(
+                    "Reports the problems impl_localize! would: syntax errors, duplicate \
+                     definitions, broken references and suspicious plural selectors",
+                )
+                .args(&Options::args())
+                .args(&output_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("usage")
+                .about(
+                    "Compares the messages Rust sources localize with the ones translations \
+                     define: reports messages that aren't defined, arguments that don't match \
+                     the translations' variables, and messages that are never localized",
+                )
+                .args(&Options::args())
+                .arg(
+                    Arg::with_name("src")
+                        .long("src")
+                        .value_name("PATH")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .default_value("src")
+                        .help("Rust files, or folders of them, to look for uses in"),
+                )
+                .args(&output_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("coverage")
+                .about(
+                    "Reports how much of the default locale each locale translates, and which \
+                     messages and attributes each one is missing",
+                )
+                .args(&Options::args())
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json", "markdown"])
+                        .default_value("text")
+                        .help("How to print the report"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("new-locale")
+                .about(
+                    "Creates the files for a new locale, mirroring the default locale's, with \
+                     every message stubbed out for translation",
+                )
+                .arg(
+                    Arg::with_name("locale")
+                        .value_name("LOCALE")
+                        .required(true)
+                        .help("The locale to create, like fr_FR"),
+                )
+                .args(&Options::args())
+                .arg(stub_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("update")
+                .about(
+                    "Adds stubs for the messages added to the default locale since other \
+                     locales were created to their files",
+                )
+                .args(&Options::args())
+                .arg(
+                    Arg::with_name("locale")
+                        .long("locale")
+                        .value_name("LOCALE")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("A locale to update; all of them if not given"),
+                )
+                .arg(stub_arg()),
+        )
+        .get_matches();
+
+    let code = match matches.subcommand() {
+        ("check", Some(matches)) => {
+            let report = match Options::from_matches(matches) {
+                Ok(options) => tree::check(&options),
+                Err(message) => Report::error(message),
+            };
+            output(matches, &report)
+        }
+        ("usage", Some(matches)) => {
+            let paths = matches
+                .values_of("src")
+                .into_iter()
+                .flatten()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            let report = match (Options::from_matches(matches), usage::read(&paths)) {
+                (Ok(options), Ok(files)) => usage::check(&options, &files),
+                (Err(message), _) => Report::error(message),
+                (_, Err(errors)) => Report {
+                    errors,
+                    ..Report::default()
+                },
+            };
+            output(matches, &report)
+        }
+        ("coverage", Some(matches)) => {
+            let loaded = Options::from_matches(matches)
+                .map_err(Report::error)
+                .and_then(|options| Ok((tree::load(&options)?, options)));
+            match loaded {
+                Ok((locales, options)) => {
+                    let coverage = baked_fluent_sources::coverage::coverage(
+                        &locales,
+                        &options.default_locale,
+                        options.on_conflict,
+                    );
+                    match matches.value_of("format") {
+                        Some("json") => println!("{}", coverage::to_json(&coverage)),
+                        Some("markdown") => print!("{}", coverage::to_markdown(&coverage)),
+                        _ => print!("{}", coverage::to_text(&coverage)),
+                    }
+                    0
+                }
+                Err(report) => output(matches, &report),
+            }
+        }
+        ("new-locale", Some(matches)) => {
+            let locale = matches.value_of("locale").unwrap_or_default();
+            write(matches, |options, stub| {
+                skeleton::new_locale(options, locale, stub)
+            })
+        }
+        ("update", Some(matches)) => {
+            let locales = matches
+                .values_of("locale")
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            write(matches, |options, stub| {
+                skeleton::update(options, &locales, stub)
+            })
+        }
+        _ => unreachable!("clap requires a subcommand"),
+    };
+    process::exit(code);
+}
+
+/// The arguments `output` reads.
+fn output_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .help("How to print the problems found"),
+        Arg::with_name("deny-warnings")
+            .long("deny-warnings")
+            .help("Fail if there are any warnings, not just errors"),
+    ]
+}
+
+fn stub_arg() -> Arg<'static, 'static> {
+    Arg::with_name("stub")
+        .long("stub")
+        .value_name("STUB")
+        .possible_values(&["comment", "copy"])
+        .default_value("comment")
+        .help(
+            "How to stub out messages: comment out the original, so that the default locale is \
+             used until it's translated, or copy it with a `# TODO: translate` comment",
+        )
+}
+
+/// Write the files for `new-locale` or `update`; returns the exit code.
+fn write(
+    matches: &ArgMatches,
+    changes: impl FnOnce(&Options, Stub) -> Result<Vec<Change>, Report>,
+) -> i32 {
+    let stub = match matches.value_of("stub") {
+        Some("copy") => Stub::Copy,
+        _ => Stub::Comment,
+    };
+    let changes = Options::from_matches(matches)
+        .map_err(Report::error)
+        .and_then(|options| changes(&options, stub));
+    let changes = match changes {
+        Ok(changes) => changes,
+        Err(report) => return output(matches, &report),
+    };
+    if changes.is_empty() {
+        println!("nothing to do: every message is translated or stubbed out");
+    }
+    for change in changes {
+        if let Err(message) = change.write() {
+            return output(matches, &Report::error(message));
+        }
+        println!("{}", change.describe());
+    }
+    0
+}
+
+/// Print a report; returns the exit code.
+fn output(matches: &ArgMatches, report: &Report) -> i32 {
+    if matches.value_of("format") == Some("json") {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text());
+    }
+    if report.failed(matches.is_present("deny-warnings")) {
+        1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,122 @@
+//! The problems found in a translation tree, as text or JSON.
+
+use serde_json::{json, Value};
+
+/// What checking a translation tree found. Errors and warnings are descriptions made by
+/// `baked_fluent_sources`, starting with `baked_fluent: `.
+#[derive(Default)]
+pub struct Report {
+    /// The locales that were checked.
+    pub locales: Vec<String>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Report {
+    /// A report of a single error that kept the tree from being checked.
+    pub fn error(message: String) -> Report {
+        Report {
+            errors: vec![message],
+            ..Report::default()
+        }
+    }
+
+    /// Whether the check failed.
+    pub fn failed(&self, deny_warnings: bool) -> bool {
+        !self.errors.is_empty() || (deny_warnings && !self.warnings.is_empty())
+    }
+
+    /// The report for people: every problem, then a summary.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (kind, descriptions) in &[("error", &self.errors), ("warning", &self.warnings)] {
+            for description in descriptions.iter() {
+                text.push_str(&format!("{}: {}\n\n", kind, strip_prefix(description)));
+            }
+        }
+        text.push_str(&format!(
+            "checked {} locale{}: {} error{}, {} warning{}\n",
+            self.locales.len(),
+            plural(self.locales.len()),
+            self.errors.len(),
+            plural(self.errors.len()),
+            self.warnings.len(),
+            plural(self.warnings.len()),
+        ));
+        text
+    }
+
+    /// The report for tools. Each problem has a one-line `message`, which starts with
+    /// `path:line:column: ` if the problem is in a source, and the annotated `snippet` of source,
+    /// which may be empty.
+    pub fn to_json(&self) -> Value {
+        let problems = |descriptions: &[String]| {
+            descriptions
+                .iter()
+                .map(|description| {
+                    let description = strip_prefix(description);
+                    let (message, snippet) = match description.find('\n') {
+                        Some(newline) => (&description[..newline], &description[newline + 1..]),
+                        None => (description, ""),
+                    };
+                    json!({ "message": message, "snippet": snippet })
+                })
+                .collect::<Vec<_>>()
+        };
+        json!({
+            "locales": self.locales,
+            "errors": problems(&self.errors),
+            "warnings": problems(&self.warnings),
+        })
+    }
+}
+
+fn strip_prefix(description: &str) -> &str {
+    description
+        .strip_prefix("baked_fluent: ")
+        .unwrap_or(description)
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let report = Report {
+            locales: vec!["en_US".into()],
+            errors: vec![],
+            warnings: vec!["baked_fluent: a.ftl:1:7: oops\n --> a.ftl:1:7\n".into()],
+        };
+        assert!(!report.failed(false));
+        assert!(report.failed(true));
+        assert_eq!(
+            report.to_text(),
+            "warning: a.ftl:1:7: oops\n --> a.ftl:1:7\n\n\n\
+             checked 1 locale: 0 errors, 1 warning\n"
+        );
+        assert_eq!(
+            report.to_json(),
+            json!({
+                "locales": ["en_US"],
+                "errors": [],
+                "warnings": [{ "message": "a.ftl:1:7: oops", "snippet": " --> a.ftl:1:7\n" }],
+            })
+        );
+
+        let report = Report::error("baked_fluent: no such locale".into());
+        assert!(report.failed(false));
+        assert_eq!(
+            report.to_json()["errors"][0],
+            json!({ "message": "no such locale", "snippet": "" })
+        );
+    }
+}
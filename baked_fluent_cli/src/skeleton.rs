@@ -0,0 +1,437 @@
+//! Skeleton translations for a locale, made from the default locale's files: each message and term
+//! is stubbed out for a translator to fill in.
+//!
+//! Files are worked on line by line rather than through the AST, so that comments and formatting
+//! survive. Entries start at the beginning of a line; everything up to the next line that doesn't
+//! continue them (by being indented, or starting with `}`, `[`, `*` or `.`) is part of them, along
+//! with the comment directly above them.
+
+use crate::report::Report;
+use crate::tree::{self, Options};
+use baked_fluent_sources::sources::{self, Layout, Locale, Root};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How to stub out an entry.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stub {
+    /// Comment out the original, so that the default locale is used until it's translated.
+    Comment,
+    /// Copy the original, marked with `# TODO: translate`.
+    Copy,
+}
+
+/// A file to write.
+pub struct Change {
+    pub path: PathBuf,
+    pub source: String,
+    /// Whether the file is new.
+    pub created: bool,
+    /// The number of messages stubbed out in it.
+    pub messages: usize,
+}
+
+impl Change {
+    pub fn write(&self) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                format!("baked_fluent: can't create {}: {}", parent.display(), err)
+            })?;
+        }
+        fs::write(&self.path, &self.source)
+            .map_err(|err| format!("baked_fluent: can't write {}: {}", self.path.display(), err))
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}: {} message{} to translate",
+            if self.created { "created" } else { "updated" },
+            self.path.display(),
+            self.messages,
+            if self.messages == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// The files for a new locale, mirroring the default locale's.
+pub fn new_locale(options: &Options, locale: &str, stub: Stub) -> Result<Vec<Change>, Report> {
+    if !sources::looks_like_locale(locale) {
+        return Err(Report::error(format!(
+            "baked_fluent: {:?} doesn't look like a locale, like `fr_FR` or `pt-BR`",
+            locale
+        )));
+    }
+    let locales = tree::load(options)?;
+    if locales.iter().any(|existing| existing.name == locale) {
+        return Err(Report::error(format!(
+            "baked_fluent: there are already translations for {}; add new messages to them with \
+             `baked-fluent update`",
+            locale
+        )));
+    }
+    let default = default_locale(options, &locales);
+    Ok(default
+        .files
+        .iter()
+        .map(|file| {
+            let (source, messages) = skeleton(&file.source, stub, |_| true);
+            Change {
+                path: target_path(&options.roots[file.root], &file.path, &default.name, locale),
+                source,
+                created: true,
+                messages,
+            }
+        })
+        .collect())
+}
+
+/// Stubs for the messages and terms that have been added to the default locale since some
+/// locales were created (all of them, if `targets` is empty), appended to the files mirroring the
+/// ones they're in. Messages that are already stubbed out in a comment count as translated.
+pub fn update(options: &Options, targets: &[String], stub: Stub) -> Result<Vec<Change>, Report> {
+    let locales = tree::load(options)?;
+    let default = default_locale(options, &locales);
+    for target in targets {
+        if !locales.iter().any(|locale| &locale.name == target) {
+            return Err(Report::error(format!(
+                "baked_fluent: there are no translations for {}; create them with \
+                 `baked-fluent new-locale {}`",
+                target, target
+            )));
+        }
+    }
+
+    let mut changes = Vec::new();
+    for locale in &locales {
+        if locale.name == default.name || !(targets.is_empty() || targets.contains(&locale.name)) {
+            continue;
+        }
+        let present = present(locale);
+        for file in &default.files {
+            let path = target_path(
+                &options.roots[file.root],
+                &file.path,
+                &default.name,
+                &locale.name,
+            );
+            let missing = |id: &str| !present.contains(&(&file.namespace[..], id.to_string()));
+            let existing = locale.files.iter().find(|existing| existing.path == path);
+            let change = match existing {
+                Some(existing) => {
+                    let (stubs, messages) = stubs(&file.source, stub, missing);
+                    let mut source = existing.source.clone();
+                    if !source.is_empty() && !source.ends_with('\n') {
+                        source.push('\n');
+                    }
+                    source.push('\n');
+                    source.push_str(&stubs);
+                    Change {
+                        path,
+                        source,
+                        created: false,
+                        messages,
+                    }
+                }
+                None => {
+                    let (source, messages) = skeleton(&file.source, stub, missing);
+                    Change {
+                        path,
+                        source,
+                        created: true,
+                        messages,
+                    }
+                }
+            };
+            if change.messages > 0 {
+                changes.push(change);
+            }
+        }
+    }
+    Ok(changes)
+}
+
+fn default_locale<'a>(options: &Options, locales: &'a [Locale]) -> &'a Locale {
+    // `load` checks that it's there
+    locales
+        .iter()
+        .find(|locale| locale.name == options.default_locale)
+        .unwrap()
+}
+
+/// The path of the file mirroring one of another locale's.
+fn target_path(root: &Root, path: &Path, from: &str, to: &str) -> PathBuf {
+    match root.layout {
+        Layout::Dir => {
+            let relative = path.strip_prefix(root.path.join(from)).unwrap_or(path);
+            root.path.join(to).join(relative)
+        }
+        Layout::Flat => root.path.join(format!("{}.ftl", to)),
+        Layout::Suffix => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let stem = name
+                .strip_suffix(&format!(".{}.ftl", from))
+                .unwrap_or(&name);
+            root.path.join(format!("{}.{}.ftl", stem, to))
+        }
+    }
+}
+
+/// The messages and terms a locale defines or has stubbed out in a comment, by namespace.
+fn present(locale: &Locale) -> HashSet<(&str, String)> {
+    let mut present = HashSet::new();
+    for file in &locale.files {
+        for line in file.source.lines() {
+            let line = match line.strip_prefix('#') {
+                Some(commented) => commented.trim_start(),
+                None => line,
+            };
+            if let Some(id) = entry_id(line) {
+                if line[id.len()..].trim_start().starts_with('=') {
+                    present.insert((&file.namespace[..], id.to_string()));
+                }
+            }
+        }
+    }
+    present
+}
+
+/// A piece of a source file.
+enum Chunk<'a> {
+    /// A message or term, with the comment directly above it.
+    Entry {
+        id: &'a str,
+        comment: Vec<&'a str>,
+        lines: Vec<&'a str>,
+    },
+    Other(&'a str),
+}
+
+fn chunks(source: &str) -> Vec<Chunk<'_>> {
+    let lines = source.split_inclusive('\n').collect::<Vec<_>>();
+    let mut chunks = Vec::new();
+    let mut comment = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(id) = entry_id(line) {
+            // blank lines only belong to the entry if it continues after them
+            let mut end = i + 1;
+            let mut next = end;
+            while next < lines.len() {
+                if lines[next].trim().is_empty() {
+                    next += 1;
+                } else if lines[next].starts_with(&[' ', '}', '[', '*', '.'][..]) {
+                    next += 1;
+                    end = next;
+                } else {
+                    break;
+                }
+            }
+            chunks.push(Chunk::Entry {
+                id,
+                comment: comment.split_off(0),
+                lines: lines[i..end].to_vec(),
+            });
+            i = end;
+            continue;
+        }
+        if line.starts_with("# ") || line.trim_end() == "#" {
+            comment.push(line);
+        } else {
+            chunks.extend(comment.drain(..).map(Chunk::Other));
+            chunks.push(Chunk::Other(line));
+        }
+        i += 1;
+    }
+    chunks.extend(comment.into_iter().map(Chunk::Other));
+    chunks
+}
+
+/// The ID of the message or term a line starts, with a term's leading `-`.
+fn entry_id(line: &str) -> Option<&str> {
+    let name = line.strip_prefix('-').unwrap_or(line);
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let len = name
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(name.len());
+    Some(&line[..line.len() - name.len() + len])
+}
+
+fn push_stub(result: &mut String, comment: &[&str], lines: &[&str], stub: Stub) {
+    for line in comment {
+        result.push_str(line);
+    }
+    match stub {
+        Stub::Comment => {
+            for line in lines {
+                result.push_str(if line.trim().is_empty() { "#" } else { "# " });
+                result.push_str(line.trim_end_matches(&['\r', '\n'][..]));
+                result.push('\n');
+            }
+        }
+        Stub::Copy => {
+            result.push_str("# TODO: translate\n");
+            for line in lines {
+                result.push_str(line);
+            }
+            if !result.ends_with('\n') {
+                result.push('\n');
+            }
+        }
+    }
+}
+
+/// A copy of a file, with the entries `include` accepts stubbed out and the others left out.
+/// Returns the new source and the number of messages in it.
+fn skeleton(source: &str, stub: Stub, include: impl Fn(&str) -> bool) -> (String, usize) {
+    let mut result = String::new();
+    let mut messages = 0;
+    for chunk in chunks(source) {
+        match chunk {
+            Chunk::Entry { id, comment, lines } => {
+                if include(id) {
+                    push_stub(&mut result, &comment, &lines, stub);
+                    messages += !id.starts_with('-') as usize;
+                }
+            }
+            Chunk::Other(line) => result.push_str(line),
+        }
+    }
+    (result, messages)
+}
+
+/// Stubs for just the entries of a file `include` accepts, separated by blank lines.
+fn stubs(source: &str, stub: Stub, include: impl Fn(&str) -> bool) -> (String, usize) {
+    let mut result = String::new();
+    let mut messages = 0;
+    for chunk in chunks(source) {
+        if let Chunk::Entry { id, comment, lines } = chunk {
+            if include(id) {
+                if !result.is_empty() {
+                    result.push('\n');
+                }
+                push_stub(&mut result, &comment, &lines, stub);
+                messages += !id.starts_with('-') as usize;
+            }
+        }
+    }
+    (result, messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "### Greetings\n\
+                          \n\
+                          # $name is the user's name\n\
+                          greeting = Hello { $name }! { $friends ->\n\
+                          \x20   [one] You have a friend!\n\
+                          \x20  *[other] You have {$friends} friends.\n\
+                          }\n\
+                          \n\
+                          -brand = Baked Fluent\n\
+                          farewell =\n\
+                          \x20   Goodbye\n\
+                          \n\
+                          \x20   from { -brand }\n\
+                          \x20   .title = Bye\n";
+
+    #[test]
+    fn skeletons() {
+        let (commented, messages) = skeleton(SOURCE, Stub::Comment, |_| true);
+        assert_eq!(messages, 2);
+        assert_eq!(
+            commented,
+            "### Greetings\n\
+             \n\
+             # $name is the user's name\n\
+             # greeting = Hello { $name }! { $friends ->\n\
+             #     [one] You have a friend!\n\
+             #    *[other] You have {$friends} friends.\n\
+             # }\n\
+             \n\
+             # -brand = Baked Fluent\n\
+             # farewell =\n\
+             #     Goodbye\n\
+             #\n\
+             #     from { -brand }\n\
+             #     .title = Bye\n"
+        );
+        // and it's all a comment
+        assert!(commented
+            .lines()
+            .all(|line| line.is_empty() || line.starts_with('#')));
+
+        let (copied, _) = skeleton(SOURCE, Stub::Copy, |id| id != "-brand");
+        assert!(copied.starts_with(
+            "### Greetings\n\n# $name is the user's name\n# TODO: translate\ngreeting = Hello"
+        ));
+        assert!(!copied.contains("-brand ="));
+        assert!(copied.ends_with(
+            "# TODO: translate\nfarewell =\n    Goodbye\n\n    from { -brand }\n    .title = Bye\n"
+        ));
+
+        let (stubs, messages) = stubs(SOURCE, Stub::Comment, |id| id != "greeting");
+        assert_eq!(messages, 1);
+        assert!(stubs.starts_with("# -brand = Baked Fluent\n\n# farewell =\n"));
+    }
+
+    #[test]
+    fn paths() {
+        let root = |layout| Root {
+            path: PathBuf::from("i18n"),
+            layout,
+            namespace: None,
+        };
+        assert_eq!(
+            target_path(
+                &root(Layout::Dir),
+                Path::new("i18n/en_US/settings/privacy.ftl"),
+                "en_US",
+                "fr_FR"
+            ),
+            PathBuf::from("i18n/fr_FR/settings/privacy.ftl")
+        );
+        assert_eq!(
+            target_path(
+                &root(Layout::Flat),
+                Path::new("i18n/en-US.ftl"),
+                "en-US",
+                "fr-FR"
+            ),
+            PathBuf::from("i18n/fr-FR.ftl")
+        );
+        assert_eq!(
+            target_path(
+                &root(Layout::Suffix),
+                Path::new("i18n/errors.en-US.ftl"),
+                "en-US",
+                "fr-FR"
+            ),
+            PathBuf::from("i18n/errors.fr-FR.ftl")
+        );
+    }
+
+    #[test]
+    fn comments_count_as_present() {
+        let locale = Locale {
+            name: "fr_FR".into(),
+            files: vec![baked_fluent_sources::sources::SourceFile {
+                root: 0,
+                path: PathBuf::from("fr_FR/hello.ftl"),
+                namespace: String::new(),
+                source: "# greeting = Hello\n#-brand = X\ntitle = Titre\n## group = no\n".into(),
+            }],
+        };
+        let mut present = present(&locale)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect::<Vec<_>>();
+        present.sort();
+        assert_eq!(present, vec!["-brand", "greeting", "title"]);
+    }
+}
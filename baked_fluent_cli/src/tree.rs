@@ -0,0 +1,231 @@
+//! Loading and checking a translation tree, configured like `impl_localize!`.
+
+use crate::report::Report;
+use baked_fluent_sources::sources::{self, FileFilter, Layout, Locale, OnConflict, Root};
+use baked_fluent_sources::{messages, plurals, references};
+use clap::{Arg, ArgMatches};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Where translations are and how to read them: the command line version of `impl_localize!`'s
+/// attributes.
+pub struct Options {
+    pub roots: Vec<Root>,
+    pub default_locale: String,
+    pub on_conflict: OnConflict,
+    pub filter: FileFilter,
+    /// Whether the localizer also has `#[include_sources(...)]`.
+    pub included: bool,
+}
+
+impl Options {
+    /// The arguments `from_matches` reads.
+    pub fn args() -> Vec<Arg<'static, 'static>> {
+        vec![
+            Arg::with_name("paths")
+                .value_name("[NAMESPACE=]PATH")
+                .required(true)
+                .multiple(true)
+                .help(
+                    "Folders of translations, like #[path(...)]; later ones override earlier \
+                     ones. Prefix one with a namespace, as in auth=i18n/auth, to give it one",
+                ),
+            Arg::with_name("default")
+                .long("default")
+                .value_name("LOCALE")
+                .required(true)
+                .help("The default locale, like #[default_locale(...)]"),
+            Arg::with_name("layout")
+                .long("layout")
+                .value_name("LAYOUT")
+                .possible_values(&["dir", "flat", "suffix"])
+                .default_value("dir")
+                .help("How translations are laid out, like #[layout(...)]"),
+            Arg::with_name("on-conflict")
+                .long("on-conflict")
+                .value_name("POLICY")
+                .possible_values(&["override", "error"])
+                .default_value("override")
+                .help("What to do when paths define the same message, like #[on_conflict(...)]"),
+            Arg::with_name("include")
+                .long("include")
+                .value_name("GLOB")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only read matching .ftl files, like #[files(include = [...])]"),
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Leave out matching files, like #[files(exclude = [...])]"),
+            Arg::with_name("with-includes").long("with-includes").help(
+                "The localizer also has #[include_sources(...)], so references to messages \
+                 and terms these translations don't define aren't errors",
+            ),
+        ]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Result<Options, String> {
+        let layout = match matches.value_of("layout") {
+            Some("flat") => Layout::Flat,
+            Some("suffix") => Layout::Suffix,
+            _ => Layout::Dir,
+        };
+        let mut roots = Vec::new();
+        for arg in matches.values_of("paths").into_iter().flatten() {
+            let (namespace, path) = match arg.find('=') {
+                Some(eq) => (Some(arg[..eq].to_string()), &arg[eq + 1..]),
+                None => (None, arg),
+            };
+            if let Some(ref namespace) = namespace {
+                if !sources::valid_namespace(namespace) {
+                    return Err(format!(
+                        "baked_fluent: invalid namespace {:?}: must be a valid fluent identifier",
+                        namespace
+                    ));
+                }
+            }
+            roots.push(Root {
+                path: PathBuf::from(path),
+                layout,
+                namespace,
+            });
+        }
+
+        let patterns = |name| {
+            matches
+                .values_of(name)
+                .into_iter()
+                .flatten()
+                .map(FileFilter::pattern)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let include = if matches.is_present("include") {
+            Some(patterns("include")?)
+        } else {
+            None
+        };
+
+        Ok(Options {
+            roots,
+            default_locale: matches.value_of("default").unwrap_or("en_US").to_string(),
+            on_conflict: match matches.value_of("on-conflict") {
+                Some("error") => OnConflict::Error,
+                _ => OnConflict::Override,
+            },
+            filter: FileFilter::new(include, patterns("exclude")?),
+            included: matches.is_present("with-includes"),
+        })
+    }
+}
+
+/// Read the sources of every locale in a tree, in the order `impl_localize!` does.
+pub fn load(options: &Options) -> Result<Vec<Locale>, Report> {
+    for root in &options.roots {
+        sources::check_root(root).map_err(Report::error)?;
+    }
+    let available =
+        sources::available_locales(&options.roots, &options.filter).map_err(Report::error)?;
+    if !available.contains(&options.default_locale) {
+        return Err(Report::error(format!(
+            "baked_fluent: no translations for default locale {:?} (have: {:?})",
+            options.default_locale, available
+        )));
+    }
+    sources::collect_sources(&options.roots, &available, &options.filter).map_err(|errors| Report {
+        locales: available,
+        errors,
+        warnings: Vec::new(),
+    })
+}
+
+/// Check a tree for everything `impl_localize!` would complain about.
+pub fn check(options: &Options) -> Report {
+    let locales = match load(options) {
+        Ok(locales) => locales,
+        Err(report) => return report,
+    };
+    let mut report = Report {
+        locales: locales.iter().map(|locale| locale.name.clone()).collect(),
+        ..Report::default()
+    };
+    if let Err(messages) = sources::check_definitions(&locales, options.on_conflict) {
+        report.errors.extend(messages);
+    }
+    if let Err(messages) = references::check(&locales, options.on_conflict, options.included) {
+        report.errors.extend(messages);
+    }
+    report.warnings = plurals::check(&locales, options.on_conflict);
+    let definitions = locales
+        .iter()
+        .map(|locale| messages::definitions(locale, options.on_conflict))
+        .collect::<Vec<_>>();
+    let paths = definitions
+        .iter()
+        .flat_map(|definitions| definitions.keys())
+        .map(|path| &path[..])
+        .collect::<BTreeSet<_>>();
+    report.warnings.extend(messages::constant_names(paths).1);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(paths: &[&str], default_locale: &str) -> Options {
+        Options {
+            roots: paths
+                .iter()
+                .map(|path| Root {
+                    path: PathBuf::from("../baked_fluent/tests").join(path),
+                    layout: Layout::Dir,
+                    namespace: None,
+                })
+                .collect(),
+            default_locale: default_locale.into(),
+            on_conflict: OnConflict::Override,
+            filter: FileFilter::default(),
+            included: false,
+        }
+    }
+
+    #[test]
+    fn fixtures() {
+        let report = check(&options(&["i18n"], "en_US"));
+        assert_eq!(report.locales, vec!["en_US", "es_MX"]);
+        assert!(report.errors.is_empty());
+        assert!(report.warnings.is_empty());
+
+        // every syntax error is reported
+        let report = check(&options(&["ui/i18n-syntax-err"], "en_US"));
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.errors[0].contains("Expected inline expression"));
+
+        let report = check(&options(&["i18n"], "xy_ZW"));
+        assert!(report.errors[0].contains("no translations for default locale \"xy_ZW\""));
+        let report = check(&options(&["i18n-missing"], "en_US"));
+        assert!(report.errors[0].contains("can't read translations from"));
+    }
+
+    #[test]
+    fn clashing_constants() {
+        // impl_localize! warns about these, so check does too
+        let dir =
+            std::env::temp_dir().join(format!("baked_fluent_cli_clash_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("en_US")).unwrap();
+        std::fs::write(dir.join("en_US/hello.ftl"), "hello = h\nHello = H\n").unwrap();
+        let mut options = options(&[], "en_US");
+        options.roots.push(Root {
+            path: dir.clone(),
+            layout: Layout::Dir,
+            namespace: None,
+        });
+        let report = check(&options);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("`Hello`, `hello` would all be named `HELLO`"));
+    }
+}
@@ -0,0 +1,476 @@
+//! Finding the messages Rust sources localize, and comparing them with the translation tree.
+//!
+//! Uses are found by reading tokens rather than by expanding macros: the message paths and
+//! argument names given to `localize!`, `localize_into!` and `localize_into_io!`, and the ID
+//! constants `impl_localize!` generates (`Localizer::GREETING__TITLE`), which are matched against
+//! the messages in the tree. Message IDs passed around as strings aren't found.
+
+use crate::report::Report;
+use crate::tree::{self, Options};
+use baked_fluent_sources::error::{self, Location};
+use baked_fluent_sources::messages::{self, Definition};
+use proc_macro2::{Spacing, TokenStream, TokenTree};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The macros that take a message path, and the position of the path among their arguments.
+const MACROS: &[(&str, usize)] = &[
+    ("localize", 1),
+    ("localize_into", 2),
+    ("localize_into_io", 2),
+];
+
+/// A Rust source file.
+pub struct RustFile {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+/// A span of a `RustFile`.
+#[derive(Clone, Copy)]
+pub struct Span {
+    pub pos: usize,
+    pub len: usize,
+}
+
+/// A message localized somewhere.
+pub struct Use<'a> {
+    pub file: &'a RustFile,
+    pub message: Message,
+    pub span: Span,
+    /// The names of the arguments given, for macro invocations.
+    pub args: Option<Vec<(String, Span)>>,
+}
+
+pub enum Message {
+    /// A path given to a macro, like `greeting.title`.
+    Path(String),
+    /// Something that looks like an ID constant, like `GREETING__TITLE`.
+    Constant(String),
+}
+
+/// Read the `.rs` files under some files and folders, skipping hidden folders and `target`.
+pub fn read(paths: &[PathBuf]) -> Result<Vec<RustFile>, Vec<String>> {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        visit(path, &mut files, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(files)
+    } else {
+        Err(errors)
+    }
+}
+
+fn visit(path: &Path, files: &mut Vec<RustFile>, errors: &mut Vec<String>) {
+    if path.is_dir() {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                errors.push(format!(
+                    "baked_fluent: can't read {}: {}",
+                    path.display(),
+                    err
+                ));
+                return;
+            }
+        };
+        let mut children = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|child| {
+                let name = child.file_name().unwrap_or_default().to_string_lossy();
+                !(name.starts_with('.') || child.is_dir() && name == "target")
+            })
+            .collect::<Vec<_>>();
+        children.sort();
+        for child in children {
+            if child.is_dir() || child.extension().is_some_and(|ext| ext == "rs") {
+                visit(&child, files, errors);
+            }
+        }
+    } else {
+        match fs::read_to_string(path) {
+            Ok(source) => files.push(RustFile {
+                path: path.to_path_buf(),
+                source,
+            }),
+            Err(err) => errors.push(format!(
+                "baked_fluent: can't read {}: {}",
+                path.display(),
+                err
+            )),
+        }
+    }
+}
+
+/// Find the messages a file localizes, in order.
+pub fn find_uses(file: &RustFile) -> Result<Vec<Use<'_>>, String> {
+    let tokens = file.source.parse::<TokenStream>().map_err(|_| {
+        format!(
+            "baked_fluent: can't read {}: it isn't valid Rust",
+            file.path.display()
+        )
+    })?;
+    let lines = Some(0)
+        .into_iter()
+        .chain(file.source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect::<Vec<_>>();
+    let mut finder = Finder {
+        file,
+        lines,
+        uses: Vec::new(),
+    };
+    finder.find(tokens);
+    Ok(finder.uses)
+}
+
+struct Finder<'a> {
+    file: &'a RustFile,
+    /// The byte offset each line starts at.
+    lines: Vec<usize>,
+    uses: Vec<Use<'a>>,
+}
+
+impl<'a> Finder<'a> {
+    fn find(&mut self, tokens: TokenStream) {
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => {
+                    if i >= 2 && is_punct(&tokens[i - 1], '!') {
+                        if let TokenTree::Ident(name) = &tokens[i - 2] {
+                            let name = name.to_string();
+                            if let Some(&(_, position)) =
+                                MACROS.iter().find(|(macro_name, _)| *macro_name == name)
+                            {
+                                self.invocation(group.stream(), position);
+                            }
+                        }
+                    }
+                    // arguments may localize too
+                    self.find(group.stream());
+                }
+                TokenTree::Ident(ident) => {
+                    let name = ident.to_string();
+                    let constant = name.chars().next().is_some_and(|c| c.is_ascii_uppercase())
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+                    if constant
+                        && i >= 2
+                        && is_punct(&tokens[i - 1], ':')
+                        && is_punct(&tokens[i - 2], ':')
+                    {
+                        self.uses.push(Use {
+                            file: self.file,
+                            message: Message::Constant(name),
+                            span: self.span(ident.span(), ident.span()),
+                            args: None,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Record a macro invocation, given its arguments.
+    fn invocation(&mut self, tokens: TokenStream, position: usize) {
+        let mut arguments = vec![Vec::new()];
+        for token in tokens {
+            if is_punct(&token, ',') {
+                arguments.push(Vec::new());
+            } else {
+                arguments.last_mut().unwrap().push(token);
+            }
+        }
+        let path = match arguments.get(position) {
+            Some(path) if !path.is_empty() => path,
+            _ => return,
+        };
+        // `message` or `message.attribute`
+        let mut message = String::new();
+        for (i, token) in path.iter().enumerate() {
+            match token {
+                TokenTree::Ident(ident) if i % 2 == 0 => message.push_str(&ident.to_string()),
+                TokenTree::Punct(punct) if i % 2 == 1 && punct.as_char() == '.' => {
+                    message.push('.')
+                }
+                _ => return,
+            }
+        }
+        let args = arguments[position + 1..]
+            .iter()
+            .filter_map(|argument| match (argument.first(), argument.get(1)) {
+                (Some(TokenTree::Ident(key)), Some(TokenTree::Punct(eq)))
+                    if eq.as_char() == '=' && eq.spacing() == Spacing::Alone =>
+                {
+                    Some((key.to_string(), self.span(key.span(), key.span())))
+                }
+                _ => None,
+            })
+            .collect();
+        let span = self.span(path[0].span(), path[path.len() - 1].span());
+        self.uses.push(Use {
+            file: self.file,
+            message: Message::Path(message),
+            span,
+            args: Some(args),
+        });
+    }
+
+    /// The span of the source from the start of `first` to the end of `last`.
+    fn span(&self, first: proc_macro2::Span, last: proc_macro2::Span) -> Span {
+        // columns are byte offsets within their line
+        let start = first.start();
+        let end = last.end();
+        let pos = self.lines[start.line - 1] + start.column;
+        Span {
+            pos,
+            len: self.lines[end.line - 1] + end.column - pos,
+        }
+    }
+}
+
+fn is_punct(token: &TokenTree, c: char) -> bool {
+    match token {
+        TokenTree::Punct(punct) => punct.as_char() == c,
+        _ => false,
+    }
+}
+
+impl Use<'_> {
+    fn location(&self, span: Span) -> Location {
+        Location {
+            path: self.file.path.clone(),
+            source: self.file.source.clone(),
+            pos: span.pos,
+            len: span.len,
+        }
+    }
+}
+
+/// Compare the messages some Rust sources use with the ones a tree defines. Reports messages the
+/// default locale doesn't define as errors, and as warnings: arguments that aren't what the
+/// translations use, and messages that are never localized (or referenced by other messages).
+pub fn check(options: &Options, files: &[RustFile]) -> Report {
+    let locales = match tree::load(options) {
+        Ok(locales) => locales,
+        Err(report) => return report,
+    };
+    let mut report = Report {
+        locales: locales.iter().map(|locale| locale.name.clone()).collect(),
+        ..Report::default()
+    };
+    // the default locale first
+    let mut definitions = locales
+        .iter()
+        .map(|locale| {
+            (
+                &locale.name[..],
+                messages::definitions(locale, options.on_conflict),
+            )
+        })
+        .collect::<Vec<_>>();
+    definitions.sort_by_key(|(name, _)| *name != options.default_locale);
+    let paths = definitions
+        .iter()
+        .flat_map(|(_, definitions)| definitions.keys())
+        .map(|path| &path[..])
+        .collect::<BTreeSet<_>>();
+    let constants = messages::constant_names(paths)
+        .0
+        .into_iter()
+        .map(|(path, name)| (name, path))
+        .collect::<HashMap<_, _>>();
+
+    let mut used = BTreeSet::new();
+    for file in files {
+        let uses = match find_uses(file) {
+            Ok(uses) => uses,
+            Err(message) => {
+                report.errors.push(message);
+                continue;
+            }
+        };
+        for usage in uses {
+            let path = match &usage.message {
+                Message::Path(path) => &path[..],
+                Message::Constant(name) => match constants.get(name) {
+                    Some(path) => path,
+                    // some other constant
+                    None => continue,
+                },
+            };
+            used.insert(path.to_string());
+            if !definitions[0].1.contains_key(path) {
+                report.errors.push(error::describe_at(
+                    &format!(
+                        "`{}` isn't defined for the default locale {}",
+                        path, options.default_locale
+                    ),
+                    &usage.location(usage.span),
+                    "localized here",
+                ));
+                continue;
+            }
+            let args = match &usage.args {
+                Some(args) => args,
+                None => continue,
+            };
+
+            // which locales use each variable
+            let mut variables = BTreeMap::new();
+            for (locale, definitions) in &definitions {
+                for variable in definitions.get(path).iter().flat_map(|d| &d.variables) {
+                    variables
+                        .entry(&variable[..])
+                        .or_insert_with(Vec::new)
+                        .push(*locale);
+                }
+            }
+            for (arg, span) in args {
+                if !variables.contains_key(&arg[..]) {
+                    report.warnings.push(error::describe_warning(
+                        &format!("`{}` is given `${}`, but no translation uses it", path, arg),
+                        &usage.location(*span),
+                        "unused argument",
+                    ));
+                }
+            }
+            for (variable, locales) in variables {
+                if !args.iter().any(|(arg, _)| arg == variable) {
+                    report.warnings.push(error::describe_warning(
+                        &format!(
+                            "`{}` isn't given `${}`, which its translations use ({})",
+                            path,
+                            variable,
+                            locales.join(", ")
+                        ),
+                        &usage.location(usage.span),
+                        "missing argument",
+                    ));
+                }
+            }
+        }
+    }
+
+    // a message is used if it or any of its attributes is
+    let mut used_messages = BTreeSet::new();
+    let mut unused = BTreeMap::<&str, &Definition>::new();
+    for (_, definitions) in &definitions {
+        for (path, definition) in definitions {
+            if used.contains(path) {
+                used_messages.insert(&definition.message[..]);
+            }
+            for reference in &definition.references {
+                if let Some(referenced) = definitions.get(reference) {
+                    used_messages.insert(&referenced.message[..]);
+                }
+            }
+            // located in the first locale defining it
+            if *path == definition.message {
+                unused.entry(&definition.message).or_insert(definition);
+            }
+        }
+    }
+    for (message, definition) in unused {
+        if !used_messages.contains(message) {
+            report.warnings.push(error::describe_warning(
+                &format!("`{}` is never localized", message),
+                &definition.location(),
+                "defined here",
+            ));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use baked_fluent_sources::sources::{FileFilter, Layout, OnConflict, Root};
+
+    fn file(source: &str) -> RustFile {
+        RustFile {
+            path: PathBuf::from("main.rs"),
+            source: source.into(),
+        }
+    }
+
+    fn spanned(file: &RustFile, span: Span) -> &str {
+        &file.source[span.pos..span.pos + span.len]
+    }
+
+    #[test]
+    fn finds_uses() {
+        let file = file(
+            "fn main() {\n\
+             \x20   let s = \"ünïcödé\"; Ok(localize!(loc, greeting.title, name = \"Jamie\", count = f(a, b)))\n\
+             \x20   localize_into!(loc, &mut w, hello-world);\n\
+             \x20   localize_into_io!(loc, &mut w, farewell,);\n\
+             \x20   loc.localize_id(Localizer::GREETING, &[]);\n\
+             \x20   // localize!(loc, commented)\n\
+             }\n\
+             macro_rules! localize { ($x:ident) => {} }\n",
+        );
+        let uses = find_uses(&file).unwrap();
+        let found = uses
+            .iter()
+            .map(|usage| match &usage.message {
+                Message::Path(path) => path.clone(),
+                Message::Constant(name) => format!("::{}", name),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec!["greeting.title", "farewell", "::GREETING"]);
+        assert_eq!(spanned(&file, uses[0].span), "greeting.title");
+        let args = uses[0].args.as_ref().unwrap();
+        assert_eq!(args[0].0, "name");
+        assert_eq!(spanned(&file, args[1].1), "count");
+        assert!(uses[2].args.is_none());
+
+        assert!(find_uses(&self::file("fn main() {")).is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let options = Options {
+            roots: vec![Root {
+                path: PathBuf::from("../baked_fluent/tests/i18n"),
+                layout: Layout::Dir,
+                namespace: None,
+            }],
+            default_locale: "en_US".into(),
+            on_conflict: OnConflict::Override,
+            filter: FileFilter::default(),
+            included: false,
+        };
+        let files = vec![file(
+            "fn main() {\n\
+             \x20   localize!(loc, greeting, name = \"Jamie\", fiends = 2);\n\
+             \x20   localize!(loc, goodbye);\n\
+             }\n",
+        )];
+        let report = check(&options, &files);
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].starts_with(
+            "baked_fluent: main.rs:3:20: `goodbye` isn't defined for the default locale en_US"
+        ));
+        let warnings = report
+            .warnings
+            .iter()
+            .map(|warning| warning.lines().next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![
+                "baked_fluent: main.rs:2:46: `greeting` is given `$fiends`, but no translation uses it",
+                "baked_fluent: main.rs:2:20: `greeting` isn't given `$friends`, which its translations use (en_US, es_MX)",
+                "baked_fluent: ../baked_fluent/tests/i18n/en_US/hello.ftl:1:1: `title` is never localized",
+            ]
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Compression of baked sources, for the `#[compress(...)]` option.
+//! Decompression is in `baked_fluent::runtime::compress`.
+
+use proc_macro2::TokenStream;
+
+/// Compress `data` with the method named `method`, returning the matching
+/// `baked_fluent::runtime::Compression` and the compressed data.
+pub fn compress(method: &str, data: &[u8]) -> Result<(TokenStream, Vec<u8>), String> {
+    // unused if no methods are enabled
+    let _ = data;
+    match method {
+        #[cfg(feature = "with-zstd")]
+        "zstd" => Ok((
+            quote::quote!(::baked_fluent::runtime::Compression::Zstd),
+            zstd::stream::encode_all(data, 19).map_err(|e| e.to_string())?,
+        )),
+        #[cfg(feature = "with-deflate")]
+        "deflate" => {
+            use std::io::Write;
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(data).map_err(|e| e.to_string())?;
+            Ok((
+                quote::quote!(::baked_fluent::runtime::Compression::Deflate),
+                encoder.finish().map_err(|e| e.to_string())?,
+            ))
+        }
+        // only reachable if the method's feature is disabled
+        #[allow(unreachable_patterns)]
+        "zstd" | "deflate" => Err(format!(
+            "baked_fluent: {} compression requires the `with-{}` feature of baked_fluent",
+            method, method
+        )),
+        _ => Err(format!(
+            "baked_fluent: unknown compression `{}` (allowed: zstd, deflate)",
+            method
+        )),
+    }
+}
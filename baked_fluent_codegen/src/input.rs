@@ -1,21 +1,41 @@
 /// Input parser for the `impl_localize!` proc-macro.
 use syn::parse::{Parse, ParseStream, Result};
+use syn::punctuated::Punctuated;
 use syn::{bracketed, parenthesized, Ident, LitBool, LitStr, Token};
 
 /// An invocation of impl_localize
 pub struct ImplLocalize {
     pub name: Ident,
-    pub path: LitStr,
+    /// At least one; merged in order.
+    pub paths: Vec<PathArg>,
     pub default_locale: LitStr,
     pub custom_from_request: bool,
+    pub lazy: bool,
+    pub compress: Option<LitStr>,
+    pub locales: Filter,
+    pub files: Filter,
+    pub layout: Option<LitStr>,
+    pub on_conflict: Option<LitStr>,
+    /// The name of a `pub const` to export the baked sources as.
+    pub export_sources: Option<Ident>,
+    /// Paths to other localizers' exported sources, merged in order.
+    pub include_sources: Vec<syn::Path>,
 }
 
 impl Parse for ImplLocalize {
     fn parse(input: ParseStream) -> Result<Self> {
         // parse #[thing(stuff)] options
-        let mut path = None;
+        let mut paths = Vec::new();
         let mut default_locale = None;
         let mut custom_from_request = false;
+        let mut lazy = false;
+        let mut compress = None;
+        let mut locales = Filter::default();
+        let mut files = Filter::default();
+        let mut layout = None;
+        let mut on_conflict = None;
+        let mut export_sources = None;
+        let mut include_sources = Vec::new();
         loop {
             if !input.lookahead1().peek(Token![#]) {
                 break;
@@ -26,16 +46,27 @@ impl Parse for ImplLocalize {
 
             let ann_name = ann.parse::<Ident>()?;
             match &*ann_name.to_string() {
-                "path" => path = Some(Arg::<LitStr>::parse(&ann)?.value),
+                "path" => paths.push(Arg::<PathArg>::parse(&ann)?.value),
                 "default_locale" => default_locale = Some(Arg::<LitStr>::parse(&ann)?.value),
                 "custom_from_request" => {
                     custom_from_request = Arg::<LitBool>::parse(&ann)?.value.value
                 }
+                "lazy" => lazy = Arg::<LitBool>::parse(&ann)?.value.value,
+                "compress" => compress = Some(Arg::<LitStr>::parse(&ann)?.value),
+                "locales" => locales = Arg::<Filter>::parse(&ann)?.value,
+                "files" => files = Arg::<Filter>::parse(&ann)?.value,
+                "layout" => layout = Some(Arg::<LitStr>::parse(&ann)?.value),
+                "on_conflict" => on_conflict = Some(Arg::<LitStr>::parse(&ann)?.value),
+                "export_sources" => export_sources = Some(Arg::<Ident>::parse(&ann)?.value),
+                "include_sources" => include_sources.push(Arg::<syn::Path>::parse(&ann)?.value),
                 _ => {
                     return Err(syn::parse::Error::new(
                         ann_name.span(),
                         format!(
-                            "unexpected attribute `{}` (allowed: path, default_locale)",
+                            "unexpected attribute `{}` \
+                             (allowed: path, default_locale, custom_from_request, lazy, compress, \
+                             locales, files, layout, on_conflict, export_sources, \
+                             include_sources)",
                             ann_name
                         ),
                     ))
@@ -57,18 +88,105 @@ impl Parse for ImplLocalize {
         dummy.parse::<Token![_]>()?;
         input.parse::<Token![;]>()?;
 
-        let path = path.unwrap_or(LitStr::new("i18n", name.span()));
+        if paths.is_empty() {
+            paths.push(PathArg {
+                path: LitStr::new("i18n", name.span()),
+                namespace: None,
+            });
+        }
         let default_locale = default_locale.unwrap_or(LitStr::new("en_US", name.span()));
 
         Ok(ImplLocalize {
             name,
-            path,
+            paths,
             default_locale,
             custom_from_request,
+            lazy,
+            compress,
+            locales,
+            files,
+            layout,
+            on_conflict,
+            export_sources,
+            include_sources,
         })
     }
 }
 
+/// `#[path("folder")]`, or `#[path("folder", namespace = "name")]`.
+pub struct PathArg {
+    pub path: LitStr,
+    pub namespace: Option<LitStr>,
+}
+
+impl Parse for PathArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let path = input.parse::<LitStr>()?;
+        let mut namespace = None;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            let key = input.parse::<Ident>()?;
+            if key != "namespace" {
+                return Err(syn::parse::Error::new(
+                    key.span(),
+                    format!("unexpected key `{}` (allowed: namespace)", key),
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            let name = input.parse::<LitStr>()?;
+            let value = name.value();
+            if !baked_fluent_sources::sources::valid_namespace(&value) {
+                return Err(syn::parse::Error::new(
+                    name.span(),
+                    format!(
+                        "invalid namespace {:?}: must be a valid fluent identifier",
+                        value
+                    ),
+                ));
+            }
+            namespace = Some(name);
+        }
+        Ok(PathArg { path, namespace })
+    }
+}
+
+/// Lists of things to include and exclude, as in
+/// `#[locales(include = ["en_US", ...], exclude = [...])]`; both lists are optional.
+#[derive(Default)]
+pub struct Filter {
+    pub include: Option<Vec<LitStr>>,
+    pub exclude: Vec<LitStr>,
+}
+
+impl Parse for Filter {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut filter = Filter::default();
+        while !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            input.parse::<Token![=]>()?;
+            let list;
+            bracketed!(list in input);
+            let list = Punctuated::<LitStr, Token![,]>::parse_terminated(&list)?
+                .into_iter()
+                .collect();
+            match &*key.to_string() {
+                "include" => filter.include = Some(list),
+                "exclude" => filter.exclude = list,
+                _ => {
+                    return Err(syn::parse::Error::new(
+                        key.span(),
+                        format!("unexpected key `{}` (allowed: include, exclude)", key),
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(filter)
+    }
+}
+
 pub struct Arg<T: Parse> {
     pub value: T,
 }
@@ -4,14 +4,19 @@
 
 extern crate proc_macro;
 
-use proc_macro2::Ident;
-use quote::quote;
+use proc_macro2::{Ident, Literal, Span};
+use quote::{quote, quote_spanned};
 use std::env;
-use std::fs::{read_to_string, DirEntry};
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
-mod error;
+mod compress;
 mod input;
+mod locales;
+mod messages;
+
+use baked_fluent_sources::sources::{FileFilter, Layout, OnConflict, Root};
+use baked_fluent_sources::{plurals, references, sources};
+use messages::MessageIndex;
 
 macro_rules! err {
     ($span:expr, $message:expr) => {
@@ -19,6 +24,17 @@ macro_rules! err {
     };
 }
 
+/// Like `err!`, but with a compile error for each message.
+macro_rules! errs {
+    ($span:expr, $messages:expr) => {
+        return $messages
+            .into_iter()
+            .map(|message| syn::Error::new($span, message).to_compile_error())
+            .collect::<proc_macro2::TokenStream>()
+            .into();
+    };
+}
+
 /// The impl_localize macro.
 /// Generates a struct that implements `baked_fluent::Localize`, sort of like a custom derive
 /// except you don't get to see inside the struct.
@@ -32,6 +48,27 @@ macro_rules! err {
 ///     // Relative to the directory your Cargo.toml is in.
 ///     #[path("path/to/i18n/folder")]
 ///
+///     // More paths can be given; their translations are merged in order, with later paths
+///     // overriding earlier ones. A path can also have a namespace, in which case its messages
+///     // are addressed as `namespace.message` (and its terms are only visible to each other).
+///     #[path("path/to/auth/i18n", namespace = "auth")]
+///
+///     // What to do if two paths define the same message or term for the same locale:
+///     // "override" (the default) or "error".
+///     #[on_conflict("error")]
+///
+///     // Export the baked translations as `pub const I18N`, so that other crates can include
+///     // them in their own localizers.
+///     #[export_sources(I18N)]
+///
+///     // Include translations exported by another localizer, usually from a library crate.
+///     // They're merged into this localizer's locales at runtime (locales this localizer
+///     // doesn't have are left out); this localizer's own translations take precedence,
+///     // followed by later includes. There are no ID constants for included messages.
+///     // References to messages and terms this localizer doesn't define are then assumed to
+///     // refer to included ones, rather than being a compile error.
+///     #[include_sources(some_library::I18N)]
+///
 ///     // The locale to fall back to if no others can be chosen.
 ///     #[default_locale("xy_ZW")]
 ///
@@ -41,6 +78,32 @@ macro_rules! err {
 ///     // See the baked fluent `custom_from_request.rs` example for an example of this.
 ///     #[custom_from_request(true)]
 ///
+///     // Only parse each locale's translations when they're first used, instead of all of them
+///     // on the first call to `new`. Useful if you have a lot of locales; see also `warm_up`.
+///     #[lazy(true)]
+///
+///     // Compress each locale's translations in the output binary ("zstd" or "deflate"; requires
+///     // the `with-zstd` or `with-deflate` feature). They're decompressed when they're loaded,
+///     // so this works well with `lazy`.
+///     #[compress("zstd")]
+///
+///     // Which locales to include; both lists are optional, and the default locale must be
+///     // included. The `BAKED_FLUENT_LOCALES` environment variable (e.g. `en_US,es_MX`)
+///     // overrides `include` at build time, so you can build a binary for each region.
+///     #[locales(include = ["en_US", "es_MX"], exclude = ["de_DE"])]
+///
+///     // Which files to read. Every `.ftl` file in a locale's folder is read, including those in
+///     // subfolders (`en_US/settings/privacy.ftl`); these glob patterns, relative to the locale's
+///     // folder, narrow that down. Both lists are optional.
+///     #[files(include = ["**/*.ftl"], exclude = ["drafts/**"])]
+///
+///     // How translations are laid out in each path:
+///     // - "dir" (the default): `i18n/en-US/*.ftl`, including subfolders
+///     // - "flat": `i18n/en-US.ftl`
+///     // - "suffix": `i18n/messages.en-US.ftl`
+///     // Locales are named as they are in the folder or file names.
+///     #[layout("suffix")]
+///
 ///     // The struct to generate; you can change the name, the other syntax is required.
 ///     pub struct MyLocalizer(_);
 /// }
@@ -50,21 +113,86 @@ pub fn impl_localize(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(item as input::ImplLocalize);
 
     // load all source files.
-    let mut root = PathBuf::from(
+    let manifest_dir = PathBuf::from(
         &env::var("CARGO_MANIFEST_DIR").expect("baked_fluent doesn't work without cargo"),
     );
-    root.push(&ast.path.value());
-    let sources = match collect_sources(&root) {
-        Some(sources) => sources,
-        None => err!(
-            ast.path.span(),
-            "baked_fluent: .ftl source files have errors"
-        ),
+    let layout = match ast.layout {
+        None => Layout::Dir,
+        Some(ref lit) => match &*lit.value() {
+            "dir" => Layout::Dir,
+            "flat" => Layout::Flat,
+            "suffix" => Layout::Suffix,
+            other => err!(
+                lit.span(),
+                format!(
+                    "baked_fluent: unknown layout {:?} (allowed: \"dir\", \"flat\", \"suffix\")",
+                    other
+                )
+            ),
+        },
+    };
+    let roots = ast
+        .paths
+        .iter()
+        .map(|path| Root {
+            path: manifest_dir.join(path.path.value()),
+            layout,
+            namespace: path.namespace.as_ref().map(|namespace| namespace.value()),
+        })
+        .collect::<Vec<_>>();
+    let path_span = ast.paths[0].path.span();
+    for (root, path) in roots.iter().zip(&ast.paths) {
+        if let Err(message) = sources::check_root(root) {
+            err!(path.path.span(), message);
+        }
+    }
+    let on_conflict = match ast.on_conflict {
+        None => OnConflict::Override,
+        Some(ref lit) => match &*lit.value() {
+            "override" => OnConflict::Override,
+            "error" => OnConflict::Error,
+            other => err!(
+                lit.span(),
+                format!(
+                    "baked_fluent: unknown conflict policy {:?} (allowed: \"override\", \"error\")",
+                    other
+                )
+            ),
+        },
+    };
+    let file_filter = match file_filter(&ast.files) {
+        Ok(filter) => filter,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let available = match sources::available_locales(&roots, &file_filter) {
+        Ok(available) => available,
+        Err(message) => err!(path_span, message),
+    };
+    let selected = match locales::select(
+        &available,
+        &ast.locales,
+        env::var(locales::LOCALES_VAR).ok().as_deref(),
+        &ast.default_locale,
+    ) {
+        Ok(selected) => selected,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let sources = match sources::collect_sources(&roots, &selected, &file_filter) {
+        Ok(sources) => sources,
+        Err(messages) => errs!(path_span, messages),
     };
+    if let Err(messages) = sources::check_definitions(&sources, on_conflict) {
+        errs!(path_span, messages);
+    }
+    let included = !ast.include_sources.is_empty();
+    if let Err(messages) = references::check(&sources, on_conflict, included) {
+        errs!(path_span, messages);
+    }
+    let mut warnings = plurals::check(&sources, on_conflict);
 
     let locales = sources
         .iter()
-        .map(|(locale, _, _)| locale)
+        .map(|locale| &locale.name)
         .collect::<Vec<_>>();
 
     if let None = locales
@@ -85,12 +213,74 @@ pub fn impl_localize(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     // setup for invocation of quote
     let name = ast.name;
     let default_locale = ast.default_locale;
-    let includes = sources.iter().flat_map(|s| &s.1);
-    let sources = sources.iter().map(|(locale, _, sources)| {
-        quote! {
-            (#locale, &[#(#sources)*])
+    let lazy = ast.lazy;
+    let locales_var = locales::LOCALES_VAR;
+    let include_sources = &ast.include_sources;
+    let export_sources = match ast.export_sources {
+        Some(ref export) => {
+            let doc = format!(
+                "The translations baked into `{}`, for other localizers to include with \
+                 `#[include_sources(...)]`.",
+                name
+            );
+            quote! {
+                #[doc = #doc]
+                pub const #export: ::baked_fluent::runtime::ExportedSources =
+                    ::baked_fluent::runtime::ExportedSources {
+                        sources: __i18n_hidden::SOURCES,
+                        messages: __i18n_hidden::MESSAGES,
+                    };
+            }
         }
-    });
+        None => quote! {},
+    };
+    let locale_count = sources.len();
+    let index = MessageIndex::new(&sources, on_conflict);
+    let mask_words = index.mask_words;
+    let messages = index.to_tokens();
+    let (constants, clashes) = index.constants();
+    warnings.extend(clashes);
+    let warnings = warnings
+        .iter()
+        .map(|warning| gen_warning(path_span, warning));
+    let coverage = messages::coverage(&sources, &default_locale.value(), on_conflict);
+    let includes = sources
+        .iter()
+        .flat_map(|locale| &locale.files)
+        .map(|file| file.path.display().to_string());
+    let mut locale_sources = Vec::new();
+    for locale in &sources {
+        let mut namespaces = Vec::new();
+        for (namespace, files) in locale.namespaces(on_conflict) {
+            let sources = files.iter().map(|file| &file.source);
+            namespaces.push(match ast.compress {
+                Some(ref method) => {
+                    let data = sources.map(|s| &s[..]).collect::<String>();
+                    let (compression, data) =
+                        match compress::compress(&method.value(), data.as_bytes()) {
+                            Ok(compressed) => compressed,
+                            Err(message) => err!(method.span(), message),
+                        };
+                    let data = Literal::byte_string(&data);
+                    let lengths = files.iter().map(|file| file.source.len());
+                    quote! {
+                        (#namespace, LocaleSources::Compressed {
+                            compression: #compression,
+                            data: #data,
+                            lengths: &[#(#lengths),*],
+                        })
+                    }
+                }
+                None => quote! {
+                    (#namespace, LocaleSources::Plain(&[#(#sources),*]))
+                },
+            });
+        }
+        let name = &locale.name;
+        locale_sources.push(quote! {
+            (#name, &[#(#namespaces),*])
+        });
+    }
     let actix = if cfg!(feature = "with-actix") && !ast.custom_from_request {
         gen_actix(name.clone())
     } else {
@@ -99,139 +289,152 @@ pub fn impl_localize(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
 
     // generated code
     (quote! {
+        #(#warnings)*
+
         /// Internationalization support. Automatically generated from files in the `i18n` folder.
         /// For usage, see the docs of the baked_fluent::Localize trait.
-        pub struct #name(Box<[&'static str]>);
+        ///
+        /// The locale chain is stored inline, so this type is `Copy` and never allocates.
+        #[derive(Clone, Copy)]
+        pub struct #name {
+            chain: [&'static str; #locale_count],
+            locales: [u16; #locale_count],
+            mask: [u64; #mask_words],
+            len: usize,
+        }
 
         impl ::baked_fluent::Localize for #name {
             #[inline(never)]
             fn new(locale: &[&str], accept_language: Option<&str>) -> Self {
-                #name(
-                    __i18n_hidden::STATIC_PARSER
-                        .create_locale_chain(locale, accept_language)
-                        .into_boxed_slice(),
-                )
+                let mut chain = [""; #locale_count];
+                let mut locales = [0; #locale_count];
+                let mut mask = [0; #mask_words];
+                let len = __i18n_hidden::CATALOG.fill_locale_chain(
+                    locale,
+                    accept_language,
+                    &mut locales,
+                    &mut mask,
+                );
+                for i in 0..len {
+                    chain[i] = __i18n_hidden::SOURCES[locales[i] as usize].0;
+                }
+                #name { chain, locales, mask, len }
+            }
+
+            fn message_id(message: &str) -> Option<::baked_fluent::MessageId> {
+                __i18n_hidden::CATALOG.message_id(message)
             }
 
             #[inline]
-            fn localize_into<W: std::fmt::Write>(
+            fn localize_id_into<W: std::fmt::Write>(
                 &self,
                 writer: &mut W,
-                message: &'static str,
+                message: ::baked_fluent::MessageId,
                 args: &[(&str, &::baked_fluent::runtime::I18nValue)],
             ) -> ::baked_fluent::Result<()> {
-                __i18n_hidden::STATIC_PARSER.localize_into(writer, &self.0, message, args)
+                __i18n_hidden::CATALOG.localize_into(writer, &self.locales[..self.len], message, args)
             }
 
-            fn has_message(&self, message: &'static str) -> bool {
-                __i18n_hidden::STATIC_PARSER.has_message(&self.0, message)
+            fn has_message_id(&self, message: ::baked_fluent::MessageId) -> bool {
+                __i18n_hidden::CATALOG.has_message(&self.mask, message)
             }
 
             fn locale_chain(&self) -> &[&'static str] {
-                &self.0
+                &self.chain[..self.len]
             }
 
             fn default_locale() -> &'static str {
                 #default_locale
             }
+
+            fn warm_up(locales: &[&str]) {
+                __i18n_hidden::CATALOG.warm_up(locales)
+            }
+        }
+
+        impl #name {
+            #constants
+
+            /// How much of the default locale each baked in locale translates.
+            pub fn translation_coverage() -> &'static [::baked_fluent::Coverage] {
+                #coverage
+            }
         }
 
         #actix
 
+        #export_sources
+
+        /// Translations included from other localizers. Defined out here, so that the paths in
+        /// `#[include_sources(...)]` resolve like any other path in the calling module.
+        #[doc(hidden)]
+        const __I18N_INCLUDED: &[::baked_fluent::runtime::ExportedSources] =
+            &[#(#include_sources),*];
+
         #[doc(hidden)]
         mod __i18n_hidden {
-            use baked_fluent::runtime::{lazy_static, I18nValue, Resources, Sources, StaticParser};
+            use baked_fluent::runtime::{lazy_static, Catalog, I18nValue, LocaleSources, Messages, Sources};
 
             /// All sources compiled into the executable.
             pub const SOURCES: Sources = &[
-                 #(#sources),*
+                 #(#locale_sources),*
             ];
 
+            /// The locales providing each message, as bitmasks over SOURCES.
+            pub const MESSAGES: Messages = #messages;
+
             /// The parsed sources.
             lazy_static! {
-                static ref RESOURCES: Resources = Resources::new(SOURCES);
-                pub static ref STATIC_PARSER: StaticParser<'static> =
-                    StaticParser::new(&RESOURCES, #default_locale);
+                pub static ref CATALOG: Catalog =
+                    Catalog::from_static(
+                        SOURCES,
+                        MESSAGES,
+                        super::__I18N_INCLUDED,
+                        #default_locale,
+                        #lazy,
+                    );
             }
 
-            /// Necessary to get rustc to re-compile this proc macro if the included sources change.
+            /// Necessary to get rustc to re-compile this proc macro if the included sources (or the
+            /// choice of locales) change.
             #[allow(unused)]
             fn i_depend_on_these_files() {
                 #(include_bytes!(#includes);)*
+                let _ = option_env!(#locales_var);
             }
         }
     })
     .into()
 }
 
-/// Find all fluent source files from a given root.
-/// Returns a vector of (locale, [locale source paths], [locale sources])
-fn collect_sources(root: &Path) -> Option<Vec<(String, Vec<String>, Vec<String>)>> {
-    assert!(root.is_dir(), "no such directory: {:?}", root);
-
-    let mut result = vec![];
-    let mut had_errors = false;
-
-    for child in children(&root) {
-        if !child.file_type().unwrap().is_dir() {
-            // skip non-subdirectories
-            continue;
-        }
-
-        let locale = child.file_name().to_string_lossy().to_string();
-
-        let mut locale_paths = vec![];
-        let mut locale_sources = vec![];
-
-        for ftl_file in children(&child.path()) {
-            if ftl_file
-                .path()
-                .extension()
-                .map(|x| x != "ftl")
-                .unwrap_or(false)
-            {
-                // not an ftl file
-                continue;
-            }
-
-            let file_source = read_to_string(&ftl_file.path()).expect("failed to read .ftl file");
-            let path = ftl_file.path();
-
-            had_errors |= has_errors(
-                path.strip_prefix(root).expect("prefix strip failed"),
-                file_source.clone(),
-            );
-
-            locale_paths.push(path.display().to_string());
-            locale_sources.push(file_source);
-        }
-
-        if locale_paths.len() == 0 {
-            // empty directory
-            continue;
-        }
-
-        result.push((locale, locale_paths, locale_sources));
-    }
-
-    if had_errors {
-        None
-    } else {
-        Some(result)
-    }
+/// The filter for `#[files(...)]`.
+fn file_filter(files: &input::Filter) -> Result<FileFilter, syn::Error> {
+    let compile = |patterns: &[syn::LitStr]| {
+        patterns
+            .iter()
+            .map(|pattern| {
+                FileFilter::pattern(&pattern.value())
+                    .map_err(|message| syn::Error::new(pattern.span(), message))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let include = match files.include {
+        Some(ref include) => Some(compile(include)?),
+        None => None,
+    };
+    Ok(FileFilter::new(include, compile(&files.exclude)?))
 }
 
-/// If a source has errors, print them and return true.
-fn has_errors(path: &Path, source: String) -> bool {
-    match fluent_syntax::parser::parse(&source) {
-        Ok(_) => false,
-        Err((_, errs)) => {
-            eprintln!("baked_fluent: parse errors in `{}`", path.display());
-            for err in errs {
-                error::log_error(path, &source, &err);
-            }
-            true
-        }
+/// A compiler warning, from a proc macro on stable: a use of a deprecated item, whose deprecation
+/// note is the warning.
+fn gen_warning(span: Span, warning: &str) -> proc_macro2::TokenStream {
+    quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #warning)]
+            #[allow(non_camel_case_types)]
+            struct baked_fluent_warning;
+            let _ = baked_fluent_warning;
+        };
     }
 }
 
@@ -255,16 +458,3 @@ fn gen_actix(name: Ident) -> proc_macro2::TokenStream {
         }
     }
 }
-
-/// Easily find children of a directory.
-fn children(path: &Path) -> impl Iterator<Item = DirEntry> {
-    let mut results = path
-        .read_dir()
-        .expect("no such path")
-        .map(|entry| entry.expect("stop changing the filesystem underneath me"))
-        .collect::<Vec<_>>();
-
-    results.sort_by_key(DirEntry::file_name); // keep builds deterministic
-
-    results.into_iter()
-}
@@ -0,0 +1,165 @@
+//! Choosing which locales to bake in: `#[locales(...)]` and the `BAKED_FLUENT_LOCALES` variable.
+
+use crate::input::Filter;
+use syn::LitStr;
+
+/// Environment variable overriding `#[locales(include = [...])]`, as a comma-separated list
+/// (e.g. `BAKED_FLUENT_LOCALES=en_US,es_MX`). It's read when the macro is expanded.
+pub const LOCALES_VAR: &str = "BAKED_FLUENT_LOCALES";
+
+/// Decide which of the `available` locales to include, keeping their order.
+/// - `env`: the value of `LOCALES_VAR`, if set.
+///
+/// Locales named in the attribute must exist, to catch typos. Unknown locales in `env` are
+/// ignored, since one variable applies to every localizer in the build.
+/// Whatever the filter, the default locale has to be included.
+pub fn select(
+    available: &[String],
+    locales: &Filter,
+    env: Option<&str>,
+    default_locale: &LitStr,
+) -> Result<Vec<String>, syn::Error> {
+    for locale in locales.include.iter().flatten().chain(&locales.exclude) {
+        if !available.contains(&locale.value()) {
+            return Err(syn::Error::new(
+                locale.span(),
+                format!(
+                    "baked_fluent: no translations for locale {:?} (have: {:?})",
+                    locale.value(),
+                    available
+                ),
+            ));
+        }
+    }
+
+    let env = env
+        .map(|env| {
+            env.split(',')
+                .map(str::trim)
+                .filter(|locale| !locale.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .filter(|env| !env.is_empty());
+    let include = match (&env, &locales.include) {
+        (Some(env), _) => Some(env.clone()),
+        (None, Some(include)) => Some(include.iter().map(LitStr::value).collect()),
+        (None, None) => None,
+    };
+    let exclude = locales
+        .exclude
+        .iter()
+        .map(LitStr::value)
+        .collect::<Vec<_>>();
+
+    let selected = available
+        .iter()
+        .filter(|locale| {
+            include
+                .as_ref()
+                .is_none_or(|include| include.contains(locale))
+        })
+        .filter(|locale| !exclude.contains(locale))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if !selected.contains(&default_locale.value()) {
+        let reason = if exclude.contains(&default_locale.value()) {
+            "it's excluded by #[locales(exclude = [...])]".to_string()
+        } else if env.is_some() {
+            format!("it isn't listed in {}", LOCALES_VAR)
+        } else {
+            "it isn't listed in #[locales(include = [...])]".to_string()
+        };
+        return Err(syn::Error::new(
+            default_locale.span(),
+            format!(
+                "baked_fluent: default locale {:?} must be included, but {}",
+                default_locale.value(),
+                reason
+            ),
+        ));
+    }
+
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macro2::Span;
+
+    fn lit(value: &str) -> LitStr {
+        LitStr::new(value, Span::call_site())
+    }
+
+    fn select(
+        include: Option<&[&str]>,
+        exclude: &[&str],
+        env: Option<&str>,
+        default_locale: &str,
+    ) -> Result<Vec<String>, String> {
+        let available = ["de_DE", "en_US", "es_MX"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let locales = Filter {
+            include: include.map(|include| include.iter().map(|l| lit(l)).collect()),
+            exclude: exclude.iter().map(|l| lit(l)).collect(),
+        };
+        super::select(&available, &locales, env, &lit(default_locale))
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(
+            select(None, &[], None, "en_US").unwrap(),
+            vec!["de_DE", "en_US", "es_MX"]
+        );
+        assert_eq!(
+            select(Some(&["es_MX", "en_US"]), &[], None, "en_US").unwrap(),
+            vec!["en_US", "es_MX"]
+        );
+        assert_eq!(
+            select(None, &["de_DE"], None, "en_US").unwrap(),
+            vec!["en_US", "es_MX"]
+        );
+        assert_eq!(
+            select(Some(&["en_US", "es_MX"]), &["es_MX"], None, "en_US").unwrap(),
+            vec!["en_US"]
+        );
+    }
+
+    #[test]
+    fn env_overrides_include() {
+        assert_eq!(
+            select(Some(&["en_US"]), &[], Some(" en_US, de_DE,xx_XX"), "en_US").unwrap(),
+            vec!["de_DE", "en_US"]
+        );
+        assert_eq!(
+            select(None, &["de_DE"], Some("en_US,de_DE"), "en_US").unwrap(),
+            vec!["en_US"]
+        );
+        assert_eq!(
+            select(Some(&["en_US"]), &[], Some(""), "en_US").unwrap(),
+            vec!["en_US"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(select(Some(&["en_UK"]), &[], None, "en_US")
+            .unwrap_err()
+            .contains("no translations for locale \"en_UK\""));
+        assert!(select(None, &["en_US"], None, "en_US")
+            .unwrap_err()
+            .contains("excluded"));
+        assert!(select(Some(&["es_MX"]), &[], None, "en_US")
+            .unwrap_err()
+            .contains("#[locales(include"));
+        assert!(select(None, &[], Some("es_MX"), "en_US")
+            .unwrap_err()
+            .contains(LOCALES_VAR));
+    }
+}
@@ -0,0 +1,215 @@
+//! Computes which locales provide each message, so the runtime can find the right bundle
+//! without asking each one in turn.
+
+use baked_fluent_sources::coverage;
+use baked_fluent_sources::messages;
+use baked_fluent_sources::sources::{Locale, OnConflict};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::quote;
+use std::collections::BTreeMap;
+
+/// For each message (`id`) and attribute (`id.attr`), a bitmask of the locales providing it.
+/// Locales are numbered in the order the sources are baked in, and messages in sorted order;
+/// a message's number is its `MessageId`. Messages in a namespace are indexed as
+/// `namespace.id`.
+pub struct MessageIndex {
+    /// Sorted by path.
+    pub messages: BTreeMap<String, Vec<u64>>,
+    /// The number of `u64`s in each mask.
+    pub mask_words: usize,
+}
+
+impl MessageIndex {
+    /// Index the sources of each locale, as returned by `collect_sources`. Only the definitions
+    /// the runtime's bundles keep count, so that a locale is never said to provide a message
+    /// (or attribute) that only a shadowed definition has.
+    pub fn new(locales: &[Locale], on_conflict: OnConflict) -> MessageIndex {
+        let mask_words = locales.len().div_ceil(64);
+        let mut messages = BTreeMap::new();
+
+        for (locale_id, locale) in locales.iter().enumerate() {
+            for path in messages::definitions(locale, on_conflict).into_keys() {
+                let mask = messages
+                    .entry(path)
+                    .or_insert_with(|| vec![0u64; mask_words]);
+                mask[locale_id / 64] |= 1 << (locale_id % 64);
+            }
+        }
+
+        MessageIndex {
+            messages,
+            mask_words,
+        }
+    }
+
+    /// Generate the `baked_fluent::runtime::Messages` table.
+    pub fn to_tokens(&self) -> TokenStream {
+        let entries = self.messages.iter().map(|(path, mask)| {
+            let mask = mask.iter().map(|word| Literal::u64_suffixed(*word));
+            quote! {
+                (#path, &[#(#mask),*])
+            }
+        });
+        quote! {
+            &[#(#entries),*]
+        }
+    }
+
+    /// Generate an associated constant holding the `MessageId` of each message, except for
+    /// those `messages::constant_names` leaves out; returns its warnings about them.
+    pub fn constants(&self) -> (TokenStream, Vec<String>) {
+        let (names, warnings) =
+            messages::constant_names(self.messages.keys().map(|path| &path[..]));
+        let mut constants = Vec::new();
+        for (id, path) in self.messages.keys().enumerate() {
+            let name = match names.get(&path[..]) {
+                Some(name) => name,
+                None => continue,
+            };
+            let doc = format!("The ID of the `{}` message.", path);
+            let name = Ident::new(name, Span::call_site());
+            let id = id as u32;
+            constants.push(quote! {
+                #[doc = #doc]
+                pub const #name: ::baked_fluent::MessageId = ::baked_fluent::MessageId::new(#id);
+            });
+        }
+        (quote! { #(#constants)* }, warnings)
+    }
+}
+
+/// Generate the `baked_fluent::Coverage` of each locale.
+pub fn coverage(locales: &[Locale], default_locale: &str, on_conflict: OnConflict) -> TokenStream {
+    let coverage = coverage::coverage(locales, default_locale, on_conflict)
+        .into_iter()
+        .map(|coverage| {
+            let locale = &coverage.locale;
+            let total = coverage.total;
+            let missing = &coverage.missing;
+            quote! {
+                ::baked_fluent::Coverage {
+                    locale: #locale,
+                    total: #total,
+                    missing: &[#(#missing),*],
+                }
+            }
+        });
+    quote! {
+        &[#(#coverage),*]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use baked_fluent_sources::sources::SourceFile;
+    use std::path::PathBuf;
+
+    fn index(sources: &[(&str, &str)]) -> MessageIndex {
+        let locales = sources
+            .iter()
+            .map(|(locale, source)| {
+                let (namespace, source) = match source.find('|') {
+                    Some(bar) => (&source[..bar], &source[bar + 1..]),
+                    None => ("", &source[..]),
+                };
+                Locale {
+                    name: locale.to_string(),
+                    files: vec![SourceFile {
+                        root: 0,
+                        path: PathBuf::new(),
+                        namespace: namespace.to_string(),
+                        source: source.to_string(),
+                    }],
+                }
+            })
+            .collect::<Vec<_>>();
+        MessageIndex::new(&locales, OnConflict::Override)
+    }
+
+    #[test]
+    fn masks() {
+        let index = index(&[
+            ("en_US", "a = A\n    .title = T\nb = B\n-term = X"),
+            ("es_MX", "b = B"),
+            ("de_DE", "auth|login = L\n    .title = T"),
+        ]);
+        let messages = index.messages.iter().collect::<Vec<_>>();
+        assert_eq!(index.mask_words, 1);
+        assert_eq!(
+            messages,
+            vec![
+                (&"a".to_string(), &vec![0b001]),
+                (&"a.title".to_string(), &vec![0b001]),
+                (&"auth.login".to_string(), &vec![0b100]),
+                (&"auth.login.title".to_string(), &vec![0b100]),
+                (&"b".to_string(), &vec![0b011]),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowed_definitions() {
+        let file = |root, source: &str| SourceFile {
+            root,
+            path: PathBuf::new(),
+            namespace: String::new(),
+            source: source.to_string(),
+        };
+        let locales = vec![Locale {
+            name: "en_US".to_string(),
+            files: vec![file(0, "a = A\n    .extra = X"), file(1, "a = B")],
+        }];
+        let index = MessageIndex::new(&locales, OnConflict::Override);
+        assert_eq!(index.messages.keys().collect::<Vec<_>>(), vec!["a"]);
+    }
+
+    #[test]
+    fn coverage_agrees_with_masks() {
+        let file = |root, source: &str| SourceFile {
+            root,
+            path: PathBuf::new(),
+            namespace: String::new(),
+            source: source.to_string(),
+        };
+        let locales = vec![
+            Locale {
+                name: "en_US".to_string(),
+                files: vec![file(0, "a = A\n    .title = T\nb = B")],
+            },
+            Locale {
+                name: "es_MX".to_string(),
+                files: vec![file(0, "a = A\n    .title = T"), file(1, "a = B\nc = C")],
+            },
+        ];
+        let index = MessageIndex::new(&locales, OnConflict::Override);
+        let coverage = coverage::coverage(&locales, "en_US", OnConflict::Override);
+        for (locale_id, coverage) in coverage.iter().enumerate() {
+            let missing = index
+                .messages
+                .iter()
+                .filter(|(_, mask)| mask[0] & 1 != 0 && mask[0] & (1 << locale_id) == 0)
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(coverage.missing, missing, "{}", coverage.locale);
+        }
+        assert_eq!(coverage[1].missing, vec!["a.title", "b"]);
+    }
+
+    #[test]
+    fn constants() {
+        // clashing names get no constant
+        let (constants, warnings) =
+            index(&[("en_US", "a-b = A\na_b = B\nHello = H\nhello = h\nc = C")]).constants();
+        let constants = constants.to_string();
+        assert!(constants.contains("const C"), "{}", constants);
+        assert!(!constants.contains("const A_B"), "{}", constants);
+        assert!(!constants.contains("const HELLO"), "{}", constants);
+        assert_eq!(warnings.len(), 2);
+
+        // coverage is a function, so no message's constant can clash with it
+        let (constants, warnings) = index(&[("en_US", "translation-coverage = A")]).constants();
+        assert!(constants.to_string().contains("const TRANSLATION_COVERAGE"));
+        assert!(warnings.is_empty());
+    }
+}
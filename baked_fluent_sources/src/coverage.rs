@@ -0,0 +1,93 @@
+//! How much of the default locale each locale translates.
+
+use crate::messages;
+use crate::sources::{Locale, OnConflict};
+
+/// The coverage of one locale: which of the default locale's messages and attributes it defines.
+pub struct Coverage {
+    pub locale: String,
+    /// The number of messages and attributes the default locale defines.
+    pub total: usize,
+    /// The paths of the ones this locale doesn't, sorted.
+    pub missing: Vec<String>,
+}
+
+impl Coverage {
+    pub fn translated(&self) -> usize {
+        self.total - self.missing.len()
+    }
+
+    /// The percentage translated; 100 if there's nothing to translate.
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            100.0 * self.translated() as f64 / self.total as f64
+        }
+    }
+}
+
+/// The coverage of each locale, in order, relative to the default locale. Messages the default
+/// locale doesn't define don't count.
+pub fn coverage(
+    locales: &[Locale],
+    default_locale: &str,
+    on_conflict: OnConflict,
+) -> Vec<Coverage> {
+    let expected = locales
+        .iter()
+        .find(|locale| locale.name == default_locale)
+        .map(|locale| messages::definitions(locale, on_conflict))
+        .unwrap_or_default();
+    locales
+        .iter()
+        .map(|locale| {
+            let defined = messages::definitions(locale, on_conflict);
+            Coverage {
+                locale: locale.name.clone(),
+                total: expected.len(),
+                missing: expected
+                    .keys()
+                    .filter(|path| !defined.contains_key(*path))
+                    .cloned()
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::SourceFile;
+    use std::path::PathBuf;
+
+    fn locale(name: &str, source: &str) -> Locale {
+        Locale {
+            name: name.into(),
+            files: vec![SourceFile {
+                root: 0,
+                path: PathBuf::from(format!("{}.ftl", name)),
+                namespace: String::new(),
+                source: source.into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn percentages() {
+        let locales = vec![
+            locale("de_DE", "a = A\nextra = E\n"),
+            locale("en_US", "a = A\n    .title = T\nb = B\n-term = T\n"),
+            locale("es_MX", "a = A\n    .title = T\nb = B\n"),
+        ];
+        let coverage = coverage(&locales, "en_US", OnConflict::Override);
+        assert_eq!(coverage[0].locale, "de_DE");
+        assert_eq!(coverage[0].total, 3);
+        assert_eq!(coverage[0].translated(), 1);
+        assert_eq!(coverage[0].missing, vec!["a.title", "b"]);
+        assert!((coverage[0].percent() - 100.0 / 3.0).abs() < 1e-9);
+        assert!(coverage[1].missing.is_empty());
+        assert_eq!(coverage[2].percent(), 100.0);
+    }
+}
@@ -0,0 +1,276 @@
+//! Code for describing problems in fluent sources, in pretty rustc-style.
+//! Based on https://github.com/projectfluent/fluent-rs/blob/master/fluent-cli/src/main.rs
+//!
+//! Descriptions become the messages of compile errors and warnings, so that they show up wherever
+//! the compiler's diagnostics do (like an IDE's problems panel), not just in the build log. Each
+//! starts with a line saying where the problem is, followed by the annotated source.
+
+use annotate_snippets::display_list::DisplayList;
+use annotate_snippets::formatter::DisplayListFormatter;
+use annotate_snippets::snippet::{Annotation, AnnotationType, Slice, Snippet, SourceAnnotation};
+use fluent_syntax::parser::errors::{ErrorKind, ParserError};
+use std::cmp;
+use std::path::{Path, PathBuf};
+
+/// Describe a parse error.
+pub fn describe_error(path: &Path, source: &str, err: &ParserError) -> String {
+    let slice = if let Some(slice) = err.slice {
+        slice
+    } else {
+        (err.pos.0, err.pos.1)
+    };
+
+    let (id, desc) = get_error_info(&err.kind);
+    // errors at the very end of the source (like an unclosed placeable) are shown on its last
+    // character, since there's no line after a trailing newline to show
+    let last = source.char_indices().last().map_or(0, |(i, _)| i);
+    // the parser works on bytes, so its positions can be in the middle of a character
+    let pos = floor_char_boundary(source, cmp::min(err.pos.0, last));
+    let end_pos = ceil_char_boundary(source, cmp::max(pos, cmp::min(err.pos.1, slice.1)));
+    let location = Location {
+        path: path.to_path_buf(),
+        source: source.to_string(),
+        pos,
+        len: end_pos - pos,
+    };
+    // show the rest of the entry from the line the error is on, so the snippet's position
+    // agrees with the title's
+    let start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let end = ceil_char_boundary(source, cmp::max(slice.1, end_pos));
+    // the parser's positions are byte offsets, but annotate-snippets counts characters
+    let chars = |start, end| source[start..end].chars().count();
+    let slice = Slice {
+        source: source[start..end].to_string(),
+        line_start: location.line_col().0,
+        origin: Some(path.display().to_string()),
+        fold: false,
+        annotations: vec![SourceAnnotation {
+            label: desc.to_string(),
+            annotation_type: AnnotationType::Error,
+            range: (chars(start, pos), chars(start, end_pos) + 1),
+        }],
+    };
+    describe(
+        &format!("{} ({})", desc, id),
+        &location,
+        vec![slice],
+        vec![],
+    )
+}
+
+/// The start of the character `pos` is in.
+fn floor_char_boundary(source: &str, mut pos: usize) -> usize {
+    pos = cmp::min(pos, source.len());
+    while !source.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    pos
+}
+
+/// The end of the character `pos` is in, unless it's at the start of one.
+fn ceil_char_boundary(source: &str, mut pos: usize) -> usize {
+    pos = cmp::min(pos, source.len());
+    while !source.is_char_boundary(pos) {
+        pos += 1;
+    }
+    pos
+}
+
+/// A span of a source file, like the ID of a message or term.
+#[derive(Clone)]
+pub struct Location {
+    pub path: PathBuf,
+    pub source: String,
+    /// The byte offset of the span in `source`.
+    pub pos: usize,
+    /// The length of the span, in bytes.
+    pub len: usize,
+}
+
+impl Location {
+    /// The line and column the span starts at, both starting from 1.
+    fn line_col(&self) -> (usize, usize) {
+        let before = &self.source[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    /// A slice of the line the span is on, with an annotation under the span.
+    fn slice(&self, label: &str, annotation_type: AnnotationType) -> Slice {
+        let start = self.source[..self.pos].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.source[self.pos..]
+            .find('\n')
+            .map_or(self.source.len(), |i| self.pos + i);
+        let line = &self.source[start..end];
+        // annotate-snippets counts characters, not bytes
+        let column = line[..self.pos - start].chars().count();
+        let len = self.source[self.pos..self.pos + self.len].chars().count();
+        Slice {
+            source: line.to_string(),
+            line_start: self.line_col().0,
+            origin: Some(self.path.display().to_string()),
+            fold: false,
+            annotations: vec![SourceAnnotation {
+                label: label.to_string(),
+                annotation_type,
+                range: (column, column + len),
+            }],
+        }
+    }
+}
+
+/// Describe a message or term that's defined twice; `title` is located at the second definition.
+pub fn describe_duplicate(title: &str, first: &Location, second: &Location) -> String {
+    let slices = vec![
+        first.slice("first defined here", AnnotationType::Info),
+        second.slice("defined again here", AnnotationType::Error),
+    ];
+    let footer = vec![Annotation {
+        label: Some("only the first definition would be used".to_string()),
+        id: None,
+        annotation_type: AnnotationType::Note,
+    }];
+    describe(title, second, slices, footer)
+}
+
+/// Describe an error at a single location.
+pub fn describe_at(title: &str, location: &Location, label: &str) -> String {
+    let slice = location.slice(label, AnnotationType::Error);
+    describe(title, location, vec![slice], vec![])
+}
+
+/// Describe a warning at a single location.
+pub fn describe_warning(title: &str, location: &Location, label: &str) -> String {
+    let slice = location.slice(label, AnnotationType::Warning);
+    describe(title, location, vec![slice], vec![])
+}
+
+fn describe(
+    title: &str,
+    location: &Location,
+    slices: Vec<Slice>,
+    footer: Vec<Annotation>,
+) -> String {
+    let snippet = Snippet {
+        slices,
+        title: None,
+        footer,
+    };
+    let dl = DisplayList::from(snippet);
+    // no colors: this ends up in a compiler message
+    let dlf = DisplayListFormatter::new(false);
+    let (line, column) = location.line_col();
+    format!(
+        "baked_fluent: {}:{}:{}: {}\n{}",
+        location.path.display(),
+        line,
+        column,
+        title,
+        one_based_columns(&dlf.format(&dl))
+    )
+}
+
+/// annotate-snippets numbers the columns in its `--> path:line:column` (and `:::`) lines from 0;
+/// number them from 1, like the title (and rustc) does.
+fn one_based_columns(snippet: &str) -> String {
+    snippet
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if !trimmed.starts_with("--> ") && !trimmed.starts_with("::: ") {
+                return line.to_string();
+            }
+            let colon = match line.rfind(':') {
+                Some(colon) => colon,
+                None => return line.to_string(),
+            };
+            match line[colon + 1..].parse::<usize>() {
+                Ok(column) => format!("{}:{}", &line[..colon], column + 1),
+                Err(_) => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn get_error_info(kind: &ErrorKind) -> (&'static str, String) {
+    // TODO: are these error codes defined somewhere?
+    match *kind {
+        ErrorKind::Generic => ("E0001", "Generic error".to_string()),
+        ErrorKind::ExpectedEntry => ("E0002", "Expected an entry start".to_string()),
+        ErrorKind::ExpectedToken(ch) => ("E0003", format!("Expected token: \"{}\"", ch)),
+        ErrorKind::ExpectedCharRange { ref range } => (
+            "E0004",
+            format!("Expected a character from range: \"{}\"", range),
+        ),
+        ErrorKind::ExpectedMessageField { ref entry_id } => (
+            "E0005",
+            format!(
+                "Expected message \"{}\" to have a value or attributes",
+                entry_id
+            ),
+        ),
+        ErrorKind::ExpectedTermField { ref entry_id } => (
+            "E0006",
+            format!("Expected term \"{}\" to have a value", entry_id),
+        ),
+        ErrorKind::ForbiddenWhitespace => {
+            ("E0007", "Keyword cannot end with a whitespace".to_string())
+        }
+        ErrorKind::ForbiddenCallee => (
+            "E0008",
+            "The callee has to be a simple, upper-case identifier".to_string(),
+        ),
+        ErrorKind::ForbiddenKey => ("E0009", "The key has to be a simple identifier".to_string()),
+        ErrorKind::MissingDefaultVariant => (
+            "E0010",
+            "Expected one of the variants to be marked as default **)".to_string(),
+        ),
+        ErrorKind::MissingValue => ("E0012", "Expected value.".to_string()),
+        ErrorKind::TermAttributeAsPlaceable => (
+            "E0019",
+            "Attributes of terms cannot be used as placeables".to_string(),
+        ),
+        ErrorKind::MissingVariantKey => ("E0021", "Missing variant key".to_string()),
+        ErrorKind::MissingLiteral => ("E0022", "Missing literal".to_string()),
+        ErrorKind::MultipleDefaultVariants => (
+            "E0023",
+            "Expression cannot have multiple default variants".to_string(),
+        ),
+        ErrorKind::MessageReferenceAsSelector => (
+            "E0024",
+            "Message reference cannot be used as selector".to_string(),
+        ),
+        ErrorKind::TermReferenceAsSelector => (
+            "E0025",
+            "Term reference cannot be used as selector".to_string(),
+        ),
+        ErrorKind::MessageAttributeAsSelector => (
+            "E0026",
+            "Message attribute cannot be used as selector".to_string(),
+        ),
+        ErrorKind::UnterminatedStringExpression => {
+            ("E0028", "Unterminated string expression".to_string())
+        }
+        ErrorKind::PositionalArgumentFollowsNamed => (
+            "E0029",
+            "Positional argument follows named argument".to_string(),
+        ),
+        ErrorKind::DuplicatedNamedArgument(ref arg) => {
+            ("E0030", format!("Duplicated named argument `{}`", arg))
+        }
+        ErrorKind::ForbiddenVariantAccessor => ("E0031", "Forbidden variant accessor.".to_string()),
+        ErrorKind::UnknownEscapeSequence(ref seq) => {
+            ("E0032", format!("Unknown escape sequence {:?}", seq))
+        }
+        ErrorKind::InvalidUnicodeEscapeSequence(ref seq) => {
+            ("E0033", format!("Invalid escape sequence {:?}", seq))
+        }
+        ErrorKind::UnbalancedClosingBrace => ("E0034", "Unbalanced closing brace".to_string()),
+        ErrorKind::ExpectedInlineExpression => ("E0035", "Expected inline expression".to_string()),
+        ref kind => ("E0000", format!("Other error: {:?}", kind)),
+    }
+}
@@ -0,0 +1,12 @@
+//! Finding, reading and checking the fluent sources of a translation tree.
+//!
+//! This is the part of `impl_localize!` that doesn't depend on being run by the compiler, so that
+//! the `baked-fluent` command line tool can report exactly the same problems without building
+//! anything. Errors and warnings are descriptions meant for people, starting with `baked_fluent:`.
+
+pub mod coverage;
+pub mod error;
+pub mod messages;
+pub mod plurals;
+pub mod references;
+pub mod sources;
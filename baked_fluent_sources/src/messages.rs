@@ -0,0 +1,296 @@
+//! The messages and attributes each locale defines, as the runtime sees them, and the variables
+//! (`$name`) they use.
+
+use crate::error::Location;
+use crate::sources::{Locale, OnConflict, SourceFile};
+use fluent_syntax::ast;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// A message or attribute, as defined in one locale.
+pub struct Definition<'a> {
+    /// The path of the message it belongs to: its own path, unless it's an attribute.
+    pub message: String,
+    pub file: &'a SourceFile,
+    /// The byte offset of its ID (or the attribute's) in the file.
+    pub pos: usize,
+    pub len: usize,
+    /// The variables it uses, including those used by the messages it references.
+    pub variables: BTreeSet<String>,
+    /// The messages and attributes it references, by path.
+    pub references: BTreeSet<String>,
+}
+
+impl Definition<'_> {
+    pub fn location(&self) -> Location {
+        Location {
+            path: self.file.path.clone(),
+            source: self.file.source.clone(),
+            pos: self.pos,
+            len: self.len,
+        }
+    }
+}
+
+/// The messages (`id`) and attributes (`id.attr`) a locale defines, by path, like the paths
+/// `localize!` takes: messages in a namespace have paths starting with `namespace.`. When a
+/// message is defined more than once, the definition the runtime would use is returned.
+pub fn definitions(locale: &Locale, on_conflict: OnConflict) -> BTreeMap<String, Definition<'_>> {
+    let mut definitions = BTreeMap::new();
+    for (namespace, files) in locale.namespaces(on_conflict) {
+        let prefix = if namespace.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", namespace)
+        };
+        // bundles keep the first definition of each message
+        let mut seen = HashSet::new();
+        for file in files {
+            let resource = match fluent_syntax::parser::parse(&file.source[..]) {
+                Ok(resource) => resource,
+                Err((resource, _)) => resource,
+            };
+            for entry in &resource.body {
+                let message = match entry {
+                    ast::ResourceEntry::Entry(ast::Entry::Message(message)) => message,
+                    _ => continue,
+                };
+                let id = format!("{}{}", prefix, message.id.name);
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                let patterns = Some((id.clone(), message.id.name, message.value.as_ref()))
+                    .into_iter()
+                    .chain(message.attributes.iter().map(|attribute| {
+                        (
+                            format!("{}.{}", id, attribute.id.name),
+                            attribute.id.name,
+                            Some(&attribute.value),
+                        )
+                    }));
+                for (path, name, pattern) in patterns {
+                    let mut definition = Definition {
+                        message: id.clone(),
+                        file,
+                        pos: offset(&file.source, name),
+                        len: name.len(),
+                        variables: BTreeSet::new(),
+                        references: BTreeSet::new(),
+                    };
+                    if let Some(pattern) = pattern {
+                        find_in_pattern(pattern, &prefix, &mut definition);
+                    }
+                    definitions.insert(path, definition);
+                }
+            }
+        }
+    }
+
+    // referenced messages are formatted with the same arguments
+    loop {
+        let mut changed = false;
+        let paths = definitions.keys().cloned().collect::<Vec<_>>();
+        for path in &paths {
+            let inherited = definitions[path]
+                .references
+                .iter()
+                .filter_map(|reference| definitions.get(reference))
+                .flat_map(|referenced| referenced.variables.iter().cloned())
+                .collect::<Vec<_>>();
+            let definition = definitions.get_mut(path).unwrap();
+            for variable in inherited {
+                changed |= definition.variables.insert(variable);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    definitions
+}
+
+fn find_in_pattern(pattern: &ast::Pattern, prefix: &str, definition: &mut Definition) {
+    for element in &pattern.elements {
+        if let ast::PatternElement::Placeable(expression) = element {
+            find_in_expression(expression, prefix, definition);
+        }
+    }
+}
+
+fn find_in_expression(expression: &ast::Expression, prefix: &str, definition: &mut Definition) {
+    match expression {
+        ast::Expression::InlineExpression(expression) => {
+            find_in_inline_expression(expression, prefix, definition)
+        }
+        ast::Expression::SelectExpression { selector, variants } => {
+            find_in_inline_expression(selector, prefix, definition);
+            for variant in variants {
+                find_in_pattern(&variant.value, prefix, definition);
+            }
+        }
+    }
+}
+
+fn find_in_inline_expression(
+    expression: &ast::InlineExpression,
+    prefix: &str,
+    definition: &mut Definition,
+) {
+    let arguments = match expression {
+        ast::InlineExpression::VariableReference { id } => {
+            definition.variables.insert(id.name.to_string());
+            None
+        }
+        ast::InlineExpression::MessageReference { id, attribute } => {
+            definition.references.insert(match attribute {
+                Some(attribute) => format!("{}{}.{}", prefix, id.name, attribute.name),
+                None => format!("{}{}", prefix, id.name),
+            });
+            None
+        }
+        // terms only see the arguments they're given, which are checked here
+        ast::InlineExpression::TermReference { arguments, .. }
+        | ast::InlineExpression::FunctionReference { arguments, .. } => arguments.as_ref(),
+        ast::InlineExpression::Placeable { expression } => {
+            find_in_expression(expression, prefix, definition);
+            None
+        }
+        _ => None,
+    };
+    if let Some(arguments) = arguments {
+        let named = arguments.named.iter().map(|argument| &argument.value);
+        for argument in arguments.positional.iter().chain(named) {
+            find_in_inline_expression(argument, prefix, definition);
+        }
+    }
+}
+
+/// The name of the ID constant `impl_localize!` generates for a message or attribute path:
+/// `greeting-title.alt` becomes `GREETING_TITLE__ALT`.
+pub fn constant_name(path: &str) -> String {
+    path.to_uppercase().replace('-', "_").replace('.', "__")
+}
+
+/// The ID constant of each message or attribute path of a localizer, given all of them.
+/// Paths whose constants would have the same name don't get one, since Fluent IDs are
+/// case-sensitive and `hello` and `Hello` are both valid; returns a warning for each such name.
+pub fn constant_names<'p>(
+    paths: impl IntoIterator<Item = &'p str>,
+) -> (BTreeMap<&'p str, String>, Vec<String>) {
+    let mut names: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for path in paths {
+        names.entry(constant_name(path)).or_default().push(path);
+    }
+    let mut constants = BTreeMap::new();
+    let mut warnings = Vec::new();
+    for (name, paths) in names {
+        if let [path] = paths[..] {
+            constants.insert(path, name);
+        } else {
+            warnings.push(format!(
+                "baked_fluent: messages `{}` would all be named `{}`, so none of them gets an ID \
+                 constant; look them up with `Localize::message_id` instead",
+                paths.join("`, `"),
+                name
+            ));
+        }
+    }
+    (constants, warnings)
+}
+
+/// The byte offset of a slice of `source`; identifiers in the AST are slices of the source.
+fn offset(source: &str, slice: &str) -> usize {
+    slice.as_ptr() as usize - source.as_ptr() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn locale(files: &[(&str, &str)]) -> Locale {
+        Locale {
+            name: "en_US".into(),
+            files: files
+                .iter()
+                .enumerate()
+                .map(|(i, (namespace, source))| SourceFile {
+                    root: i,
+                    path: PathBuf::from(format!("{}.ftl", i)),
+                    namespace: namespace.to_string(),
+                    source: source.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn variables() {
+        let locale = locale(&[
+            (
+                "",
+                "hello = Hello, { $name }!\n\
+                 greeting = { hello } You have { $count ->\n  [one] a message\n *[other] { $count } messages\n }.\n\
+                 \x20   .title = { -brand(platform: $os) }\n\
+                 -brand = { $platform } { $ignored }\n\
+                 a = { b }\nb = { a } { $x }\n",
+            ),
+            ("auth", "login = Log in as { $user }\nhello = { login }\n"),
+        ]);
+        let definitions = definitions(&locale, OnConflict::Override);
+        let variables = |path: &str| {
+            definitions[path]
+                .variables
+                .iter()
+                .map(|variable| &variable[..])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            definitions.keys().collect::<Vec<_>>(),
+            vec![
+                "a",
+                "auth.hello",
+                "auth.login",
+                "b",
+                "greeting",
+                "greeting.title",
+                "hello"
+            ]
+        );
+        assert_eq!(variables("hello"), vec!["name"]);
+        assert_eq!(variables("greeting"), vec!["count", "name"]);
+        assert_eq!(variables("greeting.title"), vec!["os"]);
+        assert_eq!(variables("a"), vec!["x"]);
+        assert_eq!(variables("auth.hello"), vec!["user"]);
+        assert_eq!(definitions["auth.hello"].references.len(), 1);
+        let title = &definitions["greeting.title"];
+        assert_eq!(
+            &title.file.source[title.pos..title.pos + title.len],
+            "title"
+        );
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(constant_name("greeting-title.alt"), "GREETING_TITLE__ALT");
+
+        let (constants, warnings) =
+            constant_names(vec!["a-b", "a-b.c", "a_b", "a_b-c", "Hello", "hello", "c"]);
+        assert_eq!(
+            constants.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("a-b.c", "A_B__C".to_string()),
+                ("a_b-c", "A_B_C".to_string()),
+                ("c", "C".to_string()),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "baked_fluent: messages `a-b`, `a_b` would all be named `A_B`, so none of them \
+                 gets an ID constant; look them up with `Localize::message_id` instead",
+                "baked_fluent: messages `Hello`, `hello` would all be named `HELLO`, so none of \
+                 them gets an ID constant; look them up with `Localize::message_id` instead",
+            ]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Checking plural selectors against the CLDR plural categories of each locale: translators write
+//! `[one]` in languages that don't have it, or leave out `[few]` in languages that do.
+//!
+//! These are warnings rather than errors: a selector's default variant still catches any number,
+//! just not with the right grammar.
+
+use crate::error::{self, Location};
+use crate::sources::{Locale, OnConflict, SourceFile};
+use fluent_locale::{negotiate_languages, NegotiationStrategy};
+use fluent_syntax::ast;
+use intl_pluralrules::{IntlPluralRules, PluralCategory, PluralRuleType};
+use std::collections::HashSet;
+
+/// Every plural category, in CLDR order.
+const CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// The plural categories a locale uses, in CLDR order, with the rules the runtime would pick for
+/// it (English's, for locales CLDR doesn't know).
+pub fn categories(locale: &str) -> Vec<&'static str> {
+    // same as `Bundle::new`
+    let plural_locale = negotiate_languages(
+        &[locale],
+        IntlPluralRules::get_locales(PluralRuleType::CARDINAL),
+        Some("en"),
+        &NegotiationStrategy::Lookup,
+    )[0]
+    .to_owned();
+    let rules = IntlPluralRules::create(&plural_locale, PluralRuleType::CARDINAL)
+        .expect("failed to initialize plural rules");
+
+    // The rules are code rather than data, so find out which categories they can produce by
+    // trying enough numbers: every rule in CLDR depends on at most the last three digits of the
+    // integer part, and on whether there's a fractional part (and its last digits).
+    let mut used = [false; 6];
+    let samples = (0..=1000u32)
+        .map(|n| n.to_string())
+        .chain((0..=110u32).flat_map(|n| {
+            vec![
+                format!("{}.0", n),
+                format!("{}.1", n),
+                format!("{}.5", n),
+                format!("{}.25", n),
+            ]
+        }))
+        .chain(vec!["1000000".to_string(), "1000000.0".to_string()]);
+    for sample in samples {
+        let index = match rules.select(&sample[..]) {
+            Ok(PluralCategory::ZERO) => 0,
+            Ok(PluralCategory::ONE) => 1,
+            Ok(PluralCategory::TWO) => 2,
+            Ok(PluralCategory::FEW) => 3,
+            Ok(PluralCategory::MANY) => 4,
+            Ok(PluralCategory::OTHER) | Err(_) => 5,
+        };
+        used[index] = true;
+    }
+    CATEGORIES
+        .iter()
+        .zip(&used)
+        .filter(|(_, used)| **used)
+        .map(|(category, _)| *category)
+        .collect()
+}
+
+/// Check the plural selectors in every locale's files, returning a description of each problem.
+/// Like `messages::definitions`, only the definitions the runtime's bundles keep are checked.
+pub fn check(locales: &[Locale], on_conflict: OnConflict) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for locale in locales {
+        let categories = categories(&locale.name);
+        for (_, files) in locale.namespaces(on_conflict) {
+            // bundles keep the first definition of each message or term
+            let mut seen = HashSet::new();
+            for file in files {
+                let resource = match fluent_syntax::parser::parse(&file.source[..]) {
+                    Ok(resource) => resource,
+                    Err((resource, _)) => resource,
+                };
+                let mut checker = Checker {
+                    locale: &locale.name,
+                    categories: &categories,
+                    file,
+                    entry: String::new(),
+                    warnings: &mut warnings,
+                };
+                for entry in &resource.body {
+                    match entry {
+                        ast::ResourceEntry::Entry(ast::Entry::Message(message)) => {
+                            checker.entry = message.id.name.to_string();
+                            if !seen.insert(checker.entry.clone()) {
+                                continue;
+                            }
+                            if let Some(ref value) = message.value {
+                                checker.pattern(value);
+                            }
+                            checker.attributes(&message.attributes);
+                        }
+                        ast::ResourceEntry::Entry(ast::Entry::Term(term)) => {
+                            checker.entry = format!("-{}", term.id.name);
+                            if !seen.insert(checker.entry.clone()) {
+                                continue;
+                            }
+                            checker.pattern(&term.value);
+                            checker.attributes(&term.attributes);
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// State for checking one file.
+struct Checker<'a> {
+    locale: &'a str,
+    categories: &'a [&'static str],
+    file: &'a SourceFile,
+    /// The message or term being checked.
+    entry: String,
+    warnings: &'a mut Vec<String>,
+}
+
+impl<'a> Checker<'a> {
+    fn attributes(&mut self, attributes: &[ast::Attribute]) {
+        for attribute in attributes {
+            self.pattern(&attribute.value);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &ast::Pattern) {
+        for element in &pattern.elements {
+            if let ast::PatternElement::Placeable(expression) = element {
+                self.expression(expression);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::InlineExpression(ast::InlineExpression::Placeable { expression }) => {
+                self.expression(expression)
+            }
+            ast::Expression::InlineExpression(_) => (),
+            ast::Expression::SelectExpression { selector, variants } => {
+                self.select(selector, variants);
+                for variant in variants {
+                    self.pattern(&variant.value);
+                }
+            }
+        }
+    }
+
+    fn select(&mut self, selector: &ast::InlineExpression, variants: &[ast::Variant]) {
+        let keys = variants
+            .iter()
+            .filter_map(|variant| match variant.key {
+                ast::VariantKey::Identifier { name } => Some(name),
+                ast::VariantKey::NumberLiteral { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        let (pos, len) = match selector {
+            ast::InlineExpression::FunctionReference { id, .. } if id.name == "NUMBER" => {
+                (self.offset(id.name), id.name.len())
+            }
+            // `$count` selects on a number if its variants look like it does; `[other]` alone
+            // doesn't count, since string selectors usually have a default variant named that
+            ast::InlineExpression::VariableReference { id }
+                if variants.iter().any(|variant| match variant.key {
+                    ast::VariantKey::Identifier { name } => {
+                        name != "other" && CATEGORIES.contains(&name)
+                    }
+                    ast::VariantKey::NumberLiteral { .. } => true,
+                }) =>
+            {
+                // include the `$`
+                (self.offset(id.name) - 1, id.name.len() + 1)
+            }
+            _ => return,
+        };
+        let selector = Location {
+            path: self.file.path.clone(),
+            source: self.file.source.clone(),
+            pos,
+            len,
+        };
+
+        for key in &keys {
+            if CATEGORIES.contains(key) && !self.categories.contains(key) {
+                let location = Location {
+                    pos: self.offset(key),
+                    len: key.len(),
+                    ..selector.clone()
+                };
+                self.warn(
+                    format!(
+                        "`[{}]` in `{}` can never match: {} only has the plural categories {}",
+                        key,
+                        self.entry,
+                        self.locale,
+                        self.categories.join(", ")
+                    ),
+                    &location,
+                    "impossible plural category",
+                );
+            }
+        }
+        let missing = self
+            .categories
+            .iter()
+            .filter(|category| !keys.contains(category))
+            .map(|category| format!("[{}]", category))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            self.warn(
+                format!(
+                    "`{}` doesn't have a variant for {} (plural categories of {}: {})",
+                    self.entry,
+                    missing.join(", "),
+                    self.locale,
+                    self.categories.join(", ")
+                ),
+                &selector,
+                "missing plural categories",
+            );
+        }
+    }
+
+    fn offset(&self, slice: &str) -> usize {
+        slice.as_ptr() as usize - self.file.source.as_ptr() as usize
+    }
+
+    fn warn(&mut self, message: String, location: &Location, label: &str) {
+        self.warnings
+            .push(error::describe_warning(&message, location, label));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn check(locale: &str, source: &str) -> Vec<String> {
+        let locale = Locale {
+            name: locale.into(),
+            files: vec![SourceFile {
+                root: 0,
+                path: PathBuf::from("test.ftl"),
+                namespace: String::new(),
+                source: source.into(),
+            }],
+        };
+        super::check(&[locale], OnConflict::Override)
+    }
+
+    #[test]
+    fn categories() {
+        assert_eq!(super::categories("en_US"), vec!["one", "other"]);
+        assert_eq!(super::categories("pl"), vec!["one", "few", "many", "other"]);
+        assert_eq!(super::categories("ja-JP"), vec!["other"]);
+        assert_eq!(
+            super::categories("ar"),
+            vec!["zero", "one", "two", "few", "many", "other"]
+        );
+        // like the runtime, fall back to English
+        assert_eq!(super::categories("xy_ZW"), vec!["one", "other"]);
+    }
+
+    #[test]
+    fn selectors() {
+        let friends = "friends = { $n ->\n    [one] A friend\n   *[other] { $n } friends\n}";
+        assert!(check("en_US", friends).is_empty());
+
+        let warnings = check("ja_JP", friends);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("`[one]` in `friends` can never match: ja_JP"));
+
+        let warnings = check("pl_PL", friends);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("`friends` doesn't have a variant for [few], [many]"));
+
+        // exact numbers are fine anywhere, and NUMBER() is always a plural selector
+        assert!(check(
+            "ja",
+            "a = { NUMBER($n) ->\n    [0] none\n   *[other] some\n}"
+        )
+        .is_empty());
+        let warnings = check(
+            "ja",
+            "-t = T\n    .a = { NUMBER($n) ->\n    [one] one\n   *[other] some\n}",
+        );
+        assert!(warnings[0].contains("`[one]` in `-t` can never match"));
+
+        // nested selectors are checked too
+        let nested =
+            "a = { $x ->\n   *[y] { $n ->\n        [few] few\n       *[other] many\n    }\n}";
+        // (`[few]` can't match in English, and `[one]` is missing)
+        assert_eq!(check("en", nested).len(), 2);
+    }
+
+    #[test]
+    fn string_selectors() {
+        let gender = "a = { $gender ->\n    [male] his\n    [female] her\n   *[other] their\n}";
+        assert!(check("pl", gender).is_empty());
+        let term = "-brand = B\n    .gender = masculine\na = { -brand.gender ->\n   *[one] x\n}";
+        assert!(check("ja", term).is_empty());
+    }
+
+    #[test]
+    fn shadowed_definitions() {
+        let file = |root, source: &str| SourceFile {
+            root,
+            path: PathBuf::from(format!("{}.ftl", root)),
+            namespace: String::new(),
+            source: source.into(),
+        };
+        let one = "friends = { $n ->\n    [one] A friend\n   *[other] Friends\n}";
+        let locale = || Locale {
+            name: "ja_JP".into(),
+            files: vec![file(0, one), file(1, "friends = Friends")],
+        };
+        // the later root overrides the definition with `[one]`, so the runtime never uses it
+        assert!(super::check(&[locale()], OnConflict::Override).is_empty());
+        // (it's the other way round when conflicts are errors)
+        let warnings = super::check(&[locale()], OnConflict::Error);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("0.ftl"));
+    }
+}
@@ -0,0 +1,478 @@
+//! Checking references between messages and terms (`{ other-message }`, `{ message.attribute }`,
+//! `{ -term }`): they have to resolve, and nothing may end up referencing itself.
+//!
+//! References are resolved in the bundle they're in, never in another locale, so each locale's
+//! namespaces are checked on their own, with the same precedence the runtime uses.
+
+use crate::error::{self, Location};
+use crate::sources::{Locale, OnConflict, SourceFile};
+use fluent_syntax::ast;
+use std::collections::HashMap;
+
+/// A pattern that can be referenced: the value of a message or term, or one of its attributes.
+/// Term IDs include their leading `-`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Node<'a> {
+    id: &'a str,
+    attribute: Option<&'a str>,
+}
+
+impl std::fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.attribute {
+            Some(attribute) => write!(f, "{}.{}", self.id, attribute),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// A reference in a pattern, and where it is in its source.
+struct Reference<'a> {
+    target: Node<'a>,
+    pos: usize,
+    len: usize,
+}
+
+/// A message or term definition.
+struct Definition<'a> {
+    file: &'a SourceFile,
+    value: Option<&'a ast::Pattern<'a>>,
+    attributes: &'a [ast::Attribute<'a>],
+}
+
+/// A referenceable pattern, and the references in it.
+struct Vertex<'a> {
+    node: Node<'a>,
+    file: &'a SourceFile,
+    references: Vec<Reference<'a>>,
+}
+
+/// Check every reference in every locale.
+/// - `included`: whether sources from other localizers are merged in at runtime. They may
+///   define anything, so references to messages and terms that aren't defined here are allowed.
+///
+/// Fails with a description of every broken reference and cycle found.
+pub fn check(
+    locales: &[Locale],
+    on_conflict: OnConflict,
+    included: bool,
+) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for locale in locales {
+        for (namespace, files) in locale.namespaces(on_conflict) {
+            let resources = files
+                .iter()
+                .map(|file| {
+                    let resource = match fluent_syntax::parser::parse(&file.source[..]) {
+                        Ok(resource) => resource,
+                        Err((resource, _)) => resource,
+                    };
+                    (*file, resource)
+                })
+                .collect::<Vec<_>>();
+            let context = if namespace.is_empty() {
+                format!("for locale {}", locale.name)
+            } else {
+                format!("for locale {} in namespace `{}`", locale.name, namespace)
+            };
+            check_bundle(&resources, included, &context, &mut errors);
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Check the references in the sources of one bundle, given in order of precedence, adding a
+/// description of each problem to `errors`.
+fn check_bundle<'a>(
+    resources: &'a [(&'a SourceFile, ast::Resource<'a>)],
+    included: bool,
+    context: &str,
+    errors: &mut Vec<String>,
+) {
+    // bundles keep the first definition of each message and term
+    let mut order = Vec::new();
+    let mut defined = HashMap::new();
+    for (file, resource) in resources {
+        for entry in &resource.body {
+            let (id, definition) = match entry {
+                ast::ResourceEntry::Entry(ast::Entry::Message(message)) => (
+                    message.id.name,
+                    Definition {
+                        file,
+                        value: message.value.as_ref(),
+                        attributes: &message.attributes,
+                    },
+                ),
+                ast::ResourceEntry::Entry(ast::Entry::Term(term)) => (
+                    with_dash(&file.source, term.id.name),
+                    Definition {
+                        file,
+                        value: Some(&term.value),
+                        attributes: &term.attributes,
+                    },
+                ),
+                _ => continue,
+            };
+            if !defined.contains_key(id) {
+                defined.insert(id, definition);
+                order.push(id);
+            }
+        }
+    }
+
+    let mut graph = Vec::new();
+    for id in order {
+        let definition = &defined[id];
+        let patterns = definition
+            .value
+            .map(|value| (None, value))
+            .into_iter()
+            .chain(
+                definition
+                    .attributes
+                    .iter()
+                    .map(|attribute| (Some(attribute.id.name), &attribute.value)),
+            );
+        for (attribute, pattern) in patterns {
+            let node = Node { id, attribute };
+            let mut references = Vec::new();
+            find_references(&definition.file.source, pattern, &mut references);
+            for reference in &references {
+                let target = reference.target;
+                let problem = match defined.get(target.id) {
+                    None if included => continue,
+                    None => format!("`{}` isn't defined", target.id),
+                    Some(definition) => match target.attribute {
+                        Some(attribute)
+                            if !definition
+                                .attributes
+                                .iter()
+                                .any(|attr| attr.id.name == attribute) =>
+                        {
+                            format!("`{}` has no attribute `{}`", target.id, attribute)
+                        }
+                        None if definition.value.is_none() => {
+                            format!("`{}` has no value, only attributes", target.id)
+                        }
+                        _ => continue,
+                    },
+                };
+                errors.push(error::describe_at(
+                    &format!(
+                        "`{}` references `{}`, but {} {}",
+                        node, target, problem, context
+                    ),
+                    &location(definition.file, reference),
+                    "referenced here",
+                ));
+            }
+            graph.push(Vertex {
+                node,
+                file: definition.file,
+                references,
+            });
+        }
+    }
+
+    let index = graph
+        .iter()
+        .enumerate()
+        .map(|(i, vertex)| (vertex.node, i))
+        .collect::<HashMap<_, _>>();
+    let mut visited = vec![Visit::New; graph.len()];
+    let mut cycles = Vec::new();
+    for start in 0..graph.len() {
+        find_cycles(
+            &graph,
+            &index,
+            start,
+            &mut visited,
+            &mut Vec::new(),
+            &mut cycles,
+        );
+    }
+    for (cycle, reference) in cycles {
+        let path = cycle
+            .iter()
+            .chain(Some(&cycle[0]))
+            .map(|&i| graph[i].node.to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        let closing = &graph[*cycle.last().unwrap()];
+        errors.push(error::describe_at(
+            &format!(
+                "`{}` references itself ({}) {}",
+                graph[cycle[0]].node, path, context
+            ),
+            &location(closing.file, &closing.references[reference]),
+            "the cycle closes here",
+        ));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    /// On the current path.
+    Open,
+    /// Fully explored.
+    Done,
+}
+
+/// Depth-first search for cycles through `node`'s references. `path` is the path to `node`.
+/// Adds one cycle to `cycles` for each reference that closes one: the vertices of the cycle, in
+/// order, and the index of the closing reference (in the last vertex's references).
+fn find_cycles(
+    graph: &[Vertex],
+    index: &HashMap<Node, usize>,
+    node: usize,
+    visited: &mut [Visit],
+    path: &mut Vec<usize>,
+    cycles: &mut Vec<(Vec<usize>, usize)>,
+) {
+    if visited[node] != Visit::New {
+        return;
+    }
+    visited[node] = Visit::Open;
+    path.push(node);
+    for (i, reference) in graph[node].references.iter().enumerate() {
+        // references that couldn't be resolved have been reported, or allowed by `included`
+        let target = match index.get(&reference.target) {
+            Some(&target) => target,
+            None => continue,
+        };
+        if visited[target] == Visit::Open {
+            let start = path.iter().position(|&vertex| vertex == target).unwrap();
+            cycles.push((path[start..].to_vec(), i));
+            continue;
+        }
+        find_cycles(graph, index, target, visited, path, cycles);
+    }
+    path.pop();
+    visited[node] = Visit::Done;
+}
+
+/// Collect the message and term references in a pattern.
+fn find_references<'a>(
+    source: &'a str,
+    pattern: &'a ast::Pattern<'a>,
+    references: &mut Vec<Reference<'a>>,
+) {
+    for element in &pattern.elements {
+        if let ast::PatternElement::Placeable(expression) = element {
+            find_in_expression(source, expression, references);
+        }
+    }
+}
+
+fn find_in_expression<'a>(
+    source: &'a str,
+    expression: &'a ast::Expression<'a>,
+    references: &mut Vec<Reference<'a>>,
+) {
+    match expression {
+        ast::Expression::InlineExpression(expression) => {
+            find_in_inline_expression(source, expression, references)
+        }
+        ast::Expression::SelectExpression { selector, variants } => {
+            find_in_inline_expression(source, selector, references);
+            for variant in variants {
+                find_references(source, &variant.value, references);
+            }
+        }
+    }
+}
+
+fn find_in_inline_expression<'a>(
+    source: &'a str,
+    expression: &'a ast::InlineExpression<'a>,
+    references: &mut Vec<Reference<'a>>,
+) {
+    let mut reference = |id: &'a str, attribute: &Option<ast::Identifier<'a>>| {
+        let pos = offset(source, id);
+        let end = match attribute {
+            Some(attribute) => offset(source, attribute.name) + attribute.name.len(),
+            None => pos + id.len(),
+        };
+        references.push(Reference {
+            target: Node {
+                id,
+                attribute: attribute.as_ref().map(|attribute| attribute.name),
+            },
+            pos,
+            len: end - pos,
+        });
+    };
+    let arguments = match expression {
+        ast::InlineExpression::MessageReference { id, attribute } => {
+            reference(id.name, attribute);
+            None
+        }
+        ast::InlineExpression::TermReference {
+            id,
+            attribute,
+            arguments,
+        } => {
+            reference(with_dash(source, id.name), attribute);
+            arguments.as_ref()
+        }
+        ast::InlineExpression::FunctionReference { arguments, .. } => arguments.as_ref(),
+        ast::InlineExpression::Placeable { expression } => {
+            find_in_expression(source, expression, references);
+            None
+        }
+        _ => None,
+    };
+    if let Some(arguments) = arguments {
+        let named = arguments.named.iter().map(|argument| &argument.value);
+        for argument in arguments.positional.iter().chain(named) {
+            find_in_inline_expression(source, argument, references);
+        }
+    }
+}
+
+/// The byte offset of a slice of `source`; identifiers in the AST are slices of the source.
+fn offset(source: &str, slice: &str) -> usize {
+    slice.as_ptr() as usize - source.as_ptr() as usize
+}
+
+/// A term's ID with its leading `-`, which directly precedes the identifier in the source.
+fn with_dash<'a>(source: &'a str, name: &'a str) -> &'a str {
+    let pos = offset(source, name);
+    &source[pos - 1..pos + name.len()]
+}
+
+fn location(file: &SourceFile, reference: &Reference) -> Location {
+    Location {
+        path: file.path.clone(),
+        source: file.source.clone(),
+        pos: reference.pos,
+        len: reference.len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Check a locale with a file for each source; a source can be namespaced with `"ns|..."`.
+    fn check(sources: &[&str], included: bool) -> Result<(), Vec<String>> {
+        let files = sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let (namespace, source) = match source.find('|') {
+                    Some(bar) => (&source[..bar], &source[bar + 1..]),
+                    None => ("", &source[..]),
+                };
+                SourceFile {
+                    root: i,
+                    path: PathBuf::from(format!("{}.ftl", i)),
+                    namespace: namespace.to_string(),
+                    source: source.to_string(),
+                }
+            })
+            .collect();
+        let locale = Locale {
+            name: "en_US".into(),
+            files,
+        };
+        super::check(&[locale], OnConflict::Override, included)
+    }
+
+    /// The only error found checking some sources.
+    fn error(sources: &[&str], included: bool) -> String {
+        let mut errors = check(sources, included).unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        errors.remove(0)
+    }
+
+    #[test]
+    fn resolved() {
+        assert!(check(
+            &[
+                "-brand = Baked\n    .gender = masculine\n\
+                 welcome = { -brand } { greeting } { greeting.title }\n\
+                 greeting = Hi\n    .title = { -brand(case: \"upper\") }\n\
+                 sel = { -brand.gender ->\n   *[masculine] { NUMBER(-brand) }\n}",
+                "other = { welcome }",
+            ],
+            false
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn dangling() {
+        assert_eq!(
+            error(&["welcome = { -brand-name } friend"], false)
+                .lines()
+                .next()
+                .unwrap(),
+            "baked_fluent: 0.ftl:1:13: `welcome` references `-brand-name`, but `-brand-name` \
+             isn't defined for locale en_US"
+        );
+        let err = error(&["a = A\n    .b = { c.d }", "c = C"], false);
+        assert!(err.contains("`a.b` references `c.d`, but `c` has no attribute `d`"));
+        let err = error(&["a = { b }\nb =\n    .c = C"], false);
+        assert!(err.contains("`b` has no value"));
+
+        // references are nested in selectors, variants and arguments
+        for source in &[
+            "a = { { b } }",
+            "a = { $n ->\n   *[other] { b }\n}",
+            "a = { NUMBER(b) }",
+            "-t = T\na = { -t(x: b) }",
+        ] {
+            assert!(error(&[source], false).contains("`b` isn't defined"));
+        }
+
+        // namespaces are separate bundles
+        let err = error(&["-brand = Baked", "auth|login = { -brand }"], false);
+        assert!(err.contains("for locale en_US in namespace `auth`\n"));
+
+        // included sources may define anything
+        assert!(check(&["welcome = { -brand-name } friend"], true).is_ok());
+    }
+
+    #[test]
+    fn cycles() {
+        // (the later root comes first)
+        let err = error(&["a = { b }\nb = { c }", "c = { a }"], false);
+        assert!(err.contains("`c` references itself (c -> a -> b -> c)"));
+        let err = error(
+            &["-t = { -u }\n-u = { a.x }\na = A\n    .x = { -t }"],
+            false,
+        );
+        assert!(err.contains("(-t -> -u -> a.x -> -t)"));
+        assert!(check(&["a = { a }"], true).is_err());
+
+        // a message's attributes can refer to its value, and vice versa
+        assert!(check(&["a = { a.b }\n    .b = B\n    .c = { a }"], false).is_ok());
+    }
+
+    #[test]
+    fn every_problem() {
+        let errors = check(
+            &["a = { x }\nb = { y }\nc = { d }\nd = { c }\ne = { a } { e }"],
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors[0].contains("`a` references `x`"));
+        assert!(errors[1].contains("`b` references `y`"));
+        assert!(errors[2].contains("(c -> d -> c)"));
+        assert!(errors[3].contains("(e -> e)"));
+    }
+
+    #[test]
+    fn precedence() {
+        // the later root's `b` wins, so there's no cycle
+        assert!(check(&["a = { b }\nb = { a }", "b = B"], false).is_ok());
+    }
+}
@@ -0,0 +1,976 @@
+//! Finding the fluent source files of a localizer, and merging its translation roots.
+
+use crate::error;
+use fluent_syntax::ast;
+use glob::{MatchOptions, Pattern};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{read, DirEntry};
+use std::path::{Path, PathBuf};
+
+/// A translation root: a folder with the `.ftl` files of each locale, laid out as per `layout`.
+pub struct Root {
+    pub path: PathBuf,
+    pub layout: Layout,
+    /// If set, this root's messages are addressed as `namespace.message`.
+    pub namespace: Option<String>,
+}
+
+/// How the files of each locale are laid out in a translation root.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// `<root>/<locale>/<file>.ftl`, where files can also be in subfolders.
+    Dir,
+    /// `<root>/<locale>.ftl`.
+    Flat,
+    /// `<root>/<file>.<locale>.ftl`.
+    Suffix,
+}
+
+impl Layout {
+    /// The attribute value for this layout.
+    fn name(self) -> &'static str {
+        match self {
+            Layout::Dir => "dir",
+            Layout::Flat => "flat",
+            Layout::Suffix => "suffix",
+        }
+    }
+
+    /// The locale of a file directly in a translation root, if its name fits this layout.
+    fn locale_of(self, name: &str) -> Option<&str> {
+        let stem = name.strip_suffix(".ftl")?;
+        let locale = match self {
+            Layout::Dir => return None,
+            Layout::Flat => stem,
+            Layout::Suffix => &stem[stem.rfind('.')? + 1..],
+        };
+        if looks_like_locale(locale) {
+            Some(locale)
+        } else {
+            None
+        }
+    }
+}
+
+/// How to handle a message or term that more than one root defines for the same locale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OnConflict {
+    /// Later roots override earlier ones.
+    Override,
+    /// Fail to compile.
+    Error,
+}
+
+/// Whether a namespace name is valid. Namespaces are addressed like messages, so they have to
+/// look like message IDs.
+pub fn valid_namespace(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Which files in a locale's directory to read, from `#[files(include = [...], exclude = [...])]`.
+/// Patterns are globs matched against paths relative to the locale directory, like
+/// `settings/*.ftl`; `*` doesn't match `/`, but `**` matches any number of directories.
+#[derive(Default)]
+pub struct FileFilter {
+    include: Option<Vec<Pattern>>,
+    exclude: Vec<Pattern>,
+}
+
+impl FileFilter {
+    /// A filter reading the `.ftl` files that match one of the `include` patterns (or all of them,
+    /// if `include` is `None`), and leaving out anything matching one of the `exclude` patterns.
+    pub fn new(include: Option<Vec<Pattern>>, exclude: Vec<Pattern>) -> FileFilter {
+        FileFilter { include, exclude }
+    }
+
+    /// Compile a glob pattern.
+    pub fn pattern(pattern: &str) -> Result<Pattern, String> {
+        Pattern::new(pattern).map_err(|err| format!("baked_fluent: invalid glob pattern: {}", err))
+    }
+
+    fn matches(patterns: &[Pattern], path: &str) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        patterns
+            .iter()
+            .any(|pattern| pattern.matches_with(path, options))
+    }
+
+    /// Whether a file is left out entirely.
+    fn excludes(&self, path: &str) -> bool {
+        FileFilter::matches(&self.exclude, path)
+    }
+
+    /// Whether an `.ftl` file should be read.
+    fn includes(&self, path: &str) -> bool {
+        match self.include {
+            Some(ref include) => FileFilter::matches(include, path),
+            None => true,
+        }
+    }
+}
+
+/// The sources of a single locale.
+pub struct Locale {
+    pub name: String,
+    /// The locale's files from every root, in the order of the roots.
+    pub files: Vec<SourceFile>,
+}
+
+/// A single `.ftl` file.
+pub struct SourceFile {
+    /// The index of the root the file is in.
+    pub root: usize,
+    pub path: PathBuf,
+    /// The namespace of the file's messages; `""` for none.
+    pub namespace: String,
+    pub source: String,
+}
+
+impl Locale {
+    /// The locale's files grouped by namespace, the root namespace first.
+    ///
+    /// Each group becomes a bundle, which keeps the first definition of each message it sees,
+    /// so with `OnConflict::Override` the files of later roots are put first.
+    pub fn namespaces(&self, on_conflict: OnConflict) -> Vec<(&str, Vec<&SourceFile>)> {
+        let names = self
+            .files
+            .iter()
+            .map(|file| &file.namespace[..])
+            .collect::<BTreeSet<_>>();
+        names
+            .into_iter()
+            .map(|namespace| {
+                let mut files = self
+                    .files
+                    .iter()
+                    .filter(|file| file.namespace == namespace)
+                    .collect::<Vec<_>>();
+                if on_conflict == OnConflict::Override {
+                    // stable, so files from the same root keep their order
+                    files.sort_by_key(|file| std::cmp::Reverse(file.root));
+                }
+                (namespace, files)
+            })
+            .collect()
+    }
+}
+
+/// Check that a root is a directory that can be read.
+pub fn check_root(root: &Root) -> Result<(), String> {
+    match std::fs::metadata(&root.path) {
+        Ok(ref metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(format!(
+            "baked_fluent: `{}` isn't a directory",
+            root.path.display()
+        )),
+        Err(err) => Err(format!(
+            "baked_fluent: can't read translations from `{}`: {}",
+            root.path.display(),
+            err
+        )),
+    }
+}
+
+/// The locales with translations in any of the given roots, sorted.
+/// Fails if a root can't be read, or has files that don't fit its layout.
+pub fn available_locales(roots: &[Root], filter: &FileFilter) -> Result<Vec<String>, String> {
+    let mut locales = BTreeSet::new();
+    for root in roots {
+        check_root(root)?;
+        match root.layout {
+            Layout::Dir => {
+                for child in children(&root.path)? {
+                    let name = child.file_name().to_string_lossy().to_string();
+                    if name.starts_with('.') {
+                        continue;
+                    }
+                    if is_dir(&child)? {
+                        locales.insert(name);
+                    } else if !filter.excludes(&name) {
+                        if let Some(layout) = guess_layout(&name) {
+                            return Err(format!(
+                                "baked_fluent: found `{}`, but translations are expected in a \
+                                 folder per locale; did you mean #[layout(\"{}\")]?",
+                                child.path().display(),
+                                layout.name()
+                            ));
+                        }
+                    }
+                }
+            }
+            Layout::Flat | Layout::Suffix => locales.extend(
+                root_files(root, filter)?
+                    .into_iter()
+                    .map(|(locale, _)| locale),
+            ),
+        }
+    }
+    Ok(locales.into_iter().collect())
+}
+
+/// Find all fluent source files for the given locales in the given roots; in the `dir` layout,
+/// including those in subdirectories of the locale directories.
+/// Fails if any of them can't be read (or aren't UTF-8 without a byte order mark) or have
+/// errors, with a description of each, or if a file looks like it was meant to be a fluent source
+/// but isn't named like one.
+pub fn collect_sources(
+    roots: &[Root],
+    locales: &[String],
+    filter: &FileFilter,
+) -> Result<Vec<Locale>, Vec<String>> {
+    let mut result = vec![];
+    let mut errors = vec![];
+
+    for locale in locales {
+        let mut files = vec![];
+
+        for (root_index, root) in roots.iter().enumerate() {
+            let paths = match root.layout {
+                Layout::Dir => {
+                    let dir = root.path.join(locale);
+                    if !dir.is_dir() {
+                        // not every root has every locale
+                        continue;
+                    }
+                    ftl_files(&dir, filter).map_err(|message| vec![message])?
+                }
+                Layout::Flat | Layout::Suffix => root_files(root, filter)
+                    .map_err(|message| vec![message])?
+                    .into_iter()
+                    .filter(|(file_locale, _)| file_locale == locale)
+                    .map(|(_, path)| path)
+                    .collect(),
+            };
+
+            for path in paths {
+                let source = match read_source(&path) {
+                    Ok(source) => source,
+                    Err(message) => {
+                        errors.push(message);
+                        continue;
+                    }
+                };
+
+                errors.extend(parse_errors(&path, &source));
+
+                files.push(SourceFile {
+                    root: root_index,
+                    path,
+                    namespace: root.namespace.clone().unwrap_or_default(),
+                    source,
+                });
+            }
+        }
+
+        if files.is_empty() {
+            // empty directories
+            continue;
+        }
+
+        result.push(Locale {
+            name: locale.clone(),
+            files,
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(result)
+    } else {
+        Err(errors)
+    }
+}
+
+/// The `.ftl` files in a locale directory and its subdirectories that pass a filter. Entries are
+/// visited in order of name, so builds are deterministic.
+fn ftl_files(dir: &Path, filter: &FileFilter) -> Result<Vec<PathBuf>, String> {
+    fn walk(
+        dir: &Path,
+        prefix: &str,
+        filter: &FileFilter,
+        result: &mut Vec<PathBuf>,
+    ) -> Result<(), String> {
+        for child in children(dir)? {
+            let name = child.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                // hidden files, editor swap files, version control...
+                continue;
+            }
+            let relative = format!("{}{}", prefix, name);
+            if filter.excludes(&relative) {
+                continue;
+            }
+
+            if is_dir(&child)? {
+                walk(&child.path(), &format!("{}/", relative), filter, result)?;
+            } else if name.ends_with(".ftl") {
+                if filter.includes(&relative) {
+                    result.push(child.path());
+                }
+            } else if looks_like_ftl(&name) {
+                return Err(misnamed(&child.path(), &relative));
+            }
+        }
+        Ok(())
+    }
+
+    let mut result = Vec::new();
+    walk(dir, "", filter, &mut result)?;
+    Ok(result)
+}
+
+/// The `.ftl` files directly in a root with the flat or suffix layout that pass a filter, along
+/// with their locales, in order of name. Fails if one doesn't fit the layout.
+fn root_files(root: &Root, filter: &FileFilter) -> Result<Vec<(String, PathBuf)>, String> {
+    let mut result = Vec::new();
+    for child in children(&root.path)? {
+        let name = child.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || filter.excludes(&name) || is_dir(&child)? {
+            continue;
+        }
+        if !name.ends_with(".ftl") {
+            if looks_like_ftl(&name) {
+                return Err(misnamed(&child.path(), &name));
+            }
+            continue;
+        }
+        if !filter.includes(&name) {
+            continue;
+        }
+        match root.layout.locale_of(&name) {
+            Some(locale) => result.push((locale.to_string(), child.path())),
+            None => {
+                let expected = match root.layout {
+                    Layout::Suffix => "<file>.<locale>.ftl",
+                    _ => "<locale>.ftl",
+                };
+                let hint = match guess_layout(&name) {
+                    Some(layout) => format!("; did you mean #[layout(\"{}\")]?", layout.name()),
+                    None => String::new(),
+                };
+                return Err(format!(
+                    "baked_fluent: `{}` isn't named like `{}`{}",
+                    child.path().display(),
+                    expected,
+                    hint
+                ));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The layout a `.ftl` file directly in a translation root was probably meant for.
+fn guess_layout(name: &str) -> Option<Layout> {
+    [Layout::Flat, Layout::Suffix]
+        .iter()
+        .cloned()
+        .find(|layout| layout.locale_of(name).is_some())
+}
+
+/// Whether a name looks like a locale: `en`, `en-US`, `en_US`, `sr-Latn-RS`...
+pub fn looks_like_locale(name: &str) -> bool {
+    let mut subtags = name.split(['-', '_']);
+    let language = subtags.next().unwrap_or("");
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// The error for a file that looks like a misnamed fluent source.
+fn misnamed(path: &Path, relative: &str) -> String {
+    format!(
+        "baked_fluent: `{}` isn't a .ftl file, so it would be ignored; rename it, \
+         or leave it out with #[files(exclude = [\"{}\"])]",
+        path.display(),
+        relative
+    )
+}
+
+/// Whether a file that isn't named `*.ftl` was probably meant to be, like `hello.FTL` or
+/// `hello.ftl.txt`.
+fn looks_like_ftl(name: &str) -> bool {
+    let lowercase = name.to_lowercase();
+    lowercase.ends_with(".ftl") || lowercase.contains(".ftl.")
+}
+
+/// Check the definitions of each locale's messages and terms:
+/// - No message or term may be defined twice in the same namespace of the same root, whether
+///   in the same file or not. (Bundles would keep the first definition, which is rarely what
+///   was meant.)
+/// - With `OnConflict::Error`, no two roots may define the same message or term in the same
+///   namespace; with `OnConflict::Override`, later roots win.
+/// - No namespace may share its name with a message outside of namespaces, since both would be
+///   addressed as `name.whatever`.
+///
+/// Fails with every problem found. Errors about duplicate definitions describe both of them.
+pub fn check_definitions(locales: &[Locale], on_conflict: OnConflict) -> Result<(), Vec<String>> {
+    let namespaces = locales
+        .iter()
+        .flat_map(|locale| &locale.files)
+        .map(|file| &file.namespace[..])
+        .filter(|namespace| !namespace.is_empty())
+        .collect::<BTreeSet<_>>();
+
+    let mut errors = Vec::new();
+    for locale in locales {
+        // the first definition of each message and term in each root, and in any root
+        let mut in_root: HashMap<(&str, String, usize), (&SourceFile, usize)> = HashMap::new();
+        let mut anywhere: HashMap<(&str, String), (&SourceFile, usize)> = HashMap::new();
+        for file in &locale.files {
+            for (id, pos) in entries(&file.source) {
+                if file.namespace.is_empty() && namespaces.contains(&id[..]) {
+                    errors.push(format!(
+                        "baked_fluent: message `{}` in `{}` has the same name as the `{}` namespace",
+                        id,
+                        file.path.display(),
+                        id
+                    ));
+                    continue;
+                }
+                let namespace = &file.namespace[..];
+                let (title, (other, other_pos)) =
+                    if let Some(other) = in_root.get(&(namespace, id.clone(), file.root)) {
+                        let title = format!(
+                            "`{}` is defined more than once for locale {}",
+                            id, locale.name
+                        );
+                        (title, *other)
+                    } else {
+                        in_root.insert((namespace, id.clone(), file.root), (file, pos));
+                        match anywhere.get(&(namespace, id.clone())) {
+                            Some(other) if on_conflict == OnConflict::Error => {
+                                let title = format!(
+                                    "`{}` is defined in both `{}` and `{}`; remove one, \
+                                     or use #[on_conflict(\"override\")] to let later paths win",
+                                    id,
+                                    other.0.path.display(),
+                                    file.path.display()
+                                );
+                                (title, *other)
+                            }
+                            Some(_) => continue,
+                            None => {
+                                anywhere.insert((namespace, id), (file, pos));
+                                continue;
+                            }
+                        }
+                    };
+                let location = |file: &'_ SourceFile, pos| error::Location {
+                    path: file.path.clone(),
+                    source: file.source.clone(),
+                    pos,
+                    len: id.len(),
+                };
+                errors.push(error::describe_duplicate(
+                    &title,
+                    &location(other, other_pos),
+                    &location(file, pos),
+                ));
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// The IDs of the messages and terms (with their leading `-`) defined by a source, along with
+/// their byte offsets in it.
+fn entries(source: &str) -> Vec<(String, usize)> {
+    let resource = match fluent_syntax::parser::parse(source) {
+        Ok(resource) => resource,
+        Err((resource, _)) => resource,
+    };
+    // identifiers are slices of the source
+    let offset = |id: &str| id.as_ptr() as usize - source.as_ptr() as usize;
+    resource
+        .body
+        .iter()
+        .filter_map(|entry| match entry {
+            ast::ResourceEntry::Entry(ast::Entry::Message(message)) => {
+                Some((message.id.name.to_string(), offset(message.id.name)))
+            }
+            ast::ResourceEntry::Entry(ast::Entry::Term(term)) => {
+                Some((format!("-{}", term.id.name), offset(term.id.name) - 1))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// A description of each of a source's parse errors.
+fn parse_errors(path: &Path, source: &str) -> Vec<String> {
+    match fluent_syntax::parser::parse(source) {
+        Ok(_) => vec![],
+        Err((_, errs)) => errs
+            .iter()
+            .map(|err| error::describe_error(path, source, err))
+            .collect(),
+    }
+}
+
+/// Read a fluent source, which has to be UTF-8, without a byte order mark.
+fn read_source(path: &Path) -> Result<String, String> {
+    let bytes = read(path)
+        .map_err(|err| format!("baked_fluent: can't read `{}`: {}", path.display(), err))?;
+    if bytes.starts_with(b"\xEF\xBB\xBF") {
+        return Err(format!(
+            "baked_fluent: `{}` starts with a byte order mark; save it as UTF-8 without one",
+            path.display()
+        ));
+    }
+    String::from_utf8(bytes).map_err(|err| {
+        let valid = &err.as_bytes()[..err.utf8_error().valid_up_to()];
+        let valid = std::str::from_utf8(valid).expect("checked by from_utf8");
+        let line_start = valid.rfind('\n').map_or(0, |i| i + 1);
+        format!(
+            "baked_fluent: `{}` isn't valid UTF-8 (at line {}, column {}); fluent sources have \
+             to be UTF-8",
+            path.display(),
+            valid.matches('\n').count() + 1,
+            valid[line_start..].chars().count() + 1
+        )
+    })
+}
+
+/// Easily find children of a directory.
+fn children(path: &Path) -> Result<Vec<DirEntry>, String> {
+    let unreadable = |err: std::io::Error| {
+        format!(
+            "baked_fluent: can't read directory `{}`: {}",
+            path.display(),
+            err
+        )
+    };
+    let mut results = path
+        .read_dir()
+        .map_err(unreadable)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(unreadable)?;
+
+    results.sort_by_key(DirEntry::file_name); // keep builds deterministic
+
+    Ok(results)
+}
+
+/// Whether a directory entry is itself a directory.
+fn is_dir(entry: &DirEntry) -> Result<bool, String> {
+    entry
+        .file_type()
+        .map(|file_type| file_type.is_dir())
+        .map_err(|err| {
+            format!(
+                "baked_fluent: can't read `{}`: {}",
+                entry.path().display(),
+                err
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(files: &[(usize, &str, &str)]) -> Locale {
+        Locale {
+            name: "en_US".into(),
+            files: files
+                .iter()
+                .enumerate()
+                .map(|(i, (root, namespace, source))| SourceFile {
+                    root: *root,
+                    path: PathBuf::from(format!("{}.ftl", i)),
+                    namespace: namespace.to_string(),
+                    source: source.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// A scratch directory with the given files in it.
+    fn scratch(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "baked_fluent_sources_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "a = A").unwrap();
+        }
+        dir
+    }
+
+    fn filter(include: Option<&[&str]>, exclude: &[&str]) -> FileFilter {
+        let patterns = |patterns: &[&str]| {
+            patterns
+                .iter()
+                .map(|p| FileFilter::pattern(p).unwrap())
+                .collect::<Vec<_>>()
+        };
+        FileFilter::new(include.map(patterns), patterns(exclude))
+    }
+
+    fn relative_paths(dir: &Path, filter: &FileFilter) -> Result<Vec<String>, String> {
+        Ok(ftl_files(dir, filter)?
+            .into_iter()
+            .map(|path| {
+                let path = path.strip_prefix(dir).unwrap();
+                let parts = path
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>();
+                parts.join("/")
+            })
+            .collect())
+    }
+
+    #[test]
+    fn recursive() {
+        let dir = scratch(
+            "recursive",
+            &[
+                "z.ftl",
+                "settings/privacy.ftl",
+                "settings/account/delete.ftl",
+                "a.ftl",
+                "README.md",
+                "noextension",
+                ".hello.ftl.swp",
+                "drafts/wip.ftl",
+            ],
+        );
+        assert_eq!(
+            relative_paths(&dir, &FileFilter::default()).unwrap(),
+            vec![
+                "a.ftl",
+                "drafts/wip.ftl",
+                "settings/account/delete.ftl",
+                "settings/privacy.ftl",
+                "z.ftl",
+            ]
+        );
+        assert_eq!(
+            relative_paths(&dir, &filter(None, &["drafts/**"])).unwrap(),
+            vec![
+                "a.ftl",
+                "settings/account/delete.ftl",
+                "settings/privacy.ftl",
+                "z.ftl"
+            ]
+        );
+        assert_eq!(
+            relative_paths(&dir, &filter(Some(&["settings/*.ftl"]), &[])).unwrap(),
+            vec!["settings/privacy.ftl"]
+        );
+        assert_eq!(
+            relative_paths(&dir, &filter(Some(&["**/*.ftl"]), &["drafts"])).unwrap(),
+            vec![
+                "a.ftl",
+                "settings/account/delete.ftl",
+                "settings/privacy.ftl",
+                "z.ftl"
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable() {
+        let dir = scratch("unreadable", &["en_US/hello.ftl"]);
+        let root = |path: &str| Root {
+            path: dir.join(path),
+            layout: Layout::Dir,
+            namespace: None,
+        };
+        assert!(check_root(&root("")).is_ok());
+        assert!(check_root(&root("missing"))
+            .unwrap_err()
+            .contains("can't read translations from"));
+        assert!(check_root(&root("en_US/hello.ftl"))
+            .unwrap_err()
+            .ends_with("hello.ftl` isn't a directory"));
+
+        let file = dir.join("en_US/hello.ftl");
+        std::fs::write(&file, b"\xEF\xBB\xBFa = A").unwrap();
+        assert!(read_source(&file)
+            .unwrap_err()
+            .contains("starts with a byte order mark"));
+        std::fs::write(&file, b"a = A\nb = \xC3\xA9\xFF").unwrap();
+        assert!(read_source(&file)
+            .unwrap_err()
+            .contains("isn't valid UTF-8 (at line 2, column 6)"));
+        assert!(read_source(&dir.join("en_US/missing.ftl"))
+            .unwrap_err()
+            .contains("can't read"));
+
+        let err = collect_sources(&[root("")], &["en_US".into()], &filter(None, &[]))
+            .err()
+            .unwrap();
+        assert_eq!(err.len(), 1);
+        assert!(err[0].contains("isn't valid UTF-8"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch("permission_denied", &["en_US/hello.ftl"]);
+        let file = dir.join("en_US/hello.ftl");
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o000)).unwrap();
+        // root can read anything, so when the tests run as root (as in many CI containers) this
+        // can't be tested; say so rather than pass silently
+        if std::fs::read(&file).is_ok() {
+            eprintln!(
+                "skipping permission_denied: `{}` is readable without permission, probably \
+                 because the tests are running as root",
+                file.display()
+            );
+            return;
+        }
+        let err = read_source(&file).unwrap_err();
+        assert!(err.starts_with("baked_fluent: can't read `"), "{}", err);
+        assert!(err.contains("Permission denied"), "{}", err);
+    }
+
+    #[test]
+    fn misnamed() {
+        assert!(looks_like_ftl("hello.ftl.txt"));
+        assert!(looks_like_ftl("hello.FTL"));
+        assert!(!looks_like_ftl("hello.ftl~"));
+        assert!(!looks_like_ftl("README.md"));
+
+        let dir = scratch("misnamed", &["hello.ftl", "nested/goodbye.ftl.txt"]);
+        let err = relative_paths(&dir, &FileFilter::default()).unwrap_err();
+        assert!(err.contains("goodbye.ftl.txt` isn't a .ftl file"));
+        assert!(err.contains("nested/goodbye.ftl.txt"));
+        assert_eq!(
+            relative_paths(&dir, &filter(None, &["**/*.txt"])).unwrap(),
+            vec!["hello.ftl"]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn layouts() {
+        let root = |path: &Path, layout| Root {
+            path: path.to_owned(),
+            layout,
+            namespace: None,
+        };
+        let no_filter = FileFilter::default();
+
+        let flat = scratch("flat", &["en-US.ftl", "es_MX.ftl", "README.md"]);
+        let roots = [root(&flat, Layout::Flat)];
+        let locales = available_locales(&roots, &no_filter).unwrap();
+        assert_eq!(locales, vec!["en-US", "es_MX"]);
+        let sources = collect_sources(&roots, &locales, &no_filter).unwrap();
+        assert_eq!(sources[1].name, "es_MX");
+        assert_eq!(sources[1].files.len(), 1);
+        assert!(available_locales(&[root(&flat, Layout::Dir)], &no_filter)
+            .unwrap_err()
+            .contains("did you mean #[layout(\"flat\")]"));
+        assert!(
+            available_locales(&[root(&flat, Layout::Suffix)], &no_filter)
+                .unwrap_err()
+                .contains(
+                    "isn't named like `<file>.<locale>.ftl`; did you mean #[layout(\"flat\")]"
+                )
+        );
+
+        let suffix = scratch(
+            "suffix",
+            &["settings.en-US.ftl", "hello.en-US.ftl", "hello.es-MX.ftl"],
+        );
+        let roots = [root(&suffix, Layout::Suffix)];
+        let locales = available_locales(&roots, &no_filter).unwrap();
+        assert_eq!(locales, vec!["en-US", "es-MX"]);
+        let sources = collect_sources(&roots, &locales, &no_filter).unwrap();
+        let names = sources[0]
+            .files
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["hello.en-US.ftl", "settings.en-US.ftl"]);
+        assert!(
+            available_locales(&[root(&suffix, Layout::Flat)], &no_filter)
+                .unwrap_err()
+                .contains("did you mean #[layout(\"suffix\")]")
+        );
+        assert_eq!(
+            available_locales(&roots, &filter(None, &["settings.*"])).unwrap(),
+            vec!["en-US", "es-MX"]
+        );
+
+        assert!(looks_like_locale("en"));
+        assert!(looks_like_locale("sr-Latn-RS"));
+        assert!(looks_like_locale("es_MX"));
+        assert!(!looks_like_locale("messages"));
+        assert!(!looks_like_locale("en-"));
+
+        std::fs::remove_dir_all(flat).unwrap();
+        std::fs::remove_dir_all(suffix).unwrap();
+    }
+
+    #[test]
+    fn namespaces() {
+        let locale = locale(&[
+            (0, "", "a = A"),
+            (0, "", "b = B"),
+            (1, "auth", "login = L"),
+            (2, "", "a = override"),
+        ]);
+        let paths = |on_conflict| {
+            locale
+                .namespaces(on_conflict)
+                .into_iter()
+                .map(|(namespace, files)| {
+                    let paths = files
+                        .iter()
+                        .map(|file| file.path.display().to_string())
+                        .collect::<Vec<_>>();
+                    (namespace, paths)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(OnConflict::Override),
+            vec![
+                ("", vec!["3.ftl".into(), "0.ftl".into(), "1.ftl".into()]),
+                ("auth", vec!["2.ftl".to_string()]),
+            ]
+        );
+        assert_eq!(
+            paths(OnConflict::Error)[0],
+            ("", vec!["0.ftl".into(), "1.ftl".into(), "3.ftl".into()])
+        );
+    }
+
+    /// The only error in a check's result.
+    fn only_error(result: Result<(), Vec<String>>) -> String {
+        let mut errors = result.unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        errors.remove(0)
+    }
+
+    #[test]
+    fn duplicates() {
+        // within a root, duplicates are an error whatever the conflict policy
+        for on_conflict in &[OnConflict::Override, OnConflict::Error] {
+            let across_files = [locale(&[(0, "", "a = A\nb = B"), (0, "", "c = C\nb = D")])];
+            let err = only_error(check_definitions(&across_files, *on_conflict));
+            let mut lines = err.lines();
+            assert_eq!(
+                lines.next().unwrap(),
+                "baked_fluent: 1.ftl:2:1: `b` is defined more than once for locale en_US"
+            );
+            let snippet = lines.collect::<Vec<_>>().join("\n");
+            assert!(snippet.contains("--> 0.ftl:2:1\n"), "{}", snippet);
+            assert!(snippet.contains("first defined here"));
+            assert!(snippet.contains("defined again here"));
+
+            let same_file = [locale(&[(0, "auth", "-t = T\n\n-t = U")])];
+            let err = only_error(check_definitions(&same_file, *on_conflict));
+            assert!(err.contains("`-t` is defined more than once"));
+
+            // even when an earlier root defines it too
+            let overridden = [locale(&[
+                (0, "", "hello = A"),
+                (1, "", "hello = B\nhello = C"),
+            ])];
+            let errors = check_definitions(&overridden, *on_conflict).unwrap_err();
+            assert!(
+                errors.iter().any(|err| err
+                    .starts_with("baked_fluent: 1.ftl:2:1: `hello` is defined more than once")),
+                "{:?}",
+                errors
+            );
+        }
+
+        // but messages and terms don't clash
+        assert!(
+            check_definitions(&[locale(&[(0, "", "a = A\n-a = T")])], OnConflict::Error).is_ok()
+        );
+    }
+
+    #[test]
+    fn parse_error_positions() {
+        // the snippet points where the title does, even at the end of the source
+        for source in &[
+            "a = A\nb = {\n",
+            "a = A\nb =\n    { }\n",
+            "a = A\n\u{e9} = {",
+        ] {
+            let errors = parse_errors(Path::new("x.ftl"), source);
+            let mut lines = errors[0].lines();
+            let title = lines.next().unwrap();
+            let snippet = lines.collect::<Vec<_>>().join("\n");
+            let mut position = title.split(':').skip(2);
+            let (line, column) = (position.next().unwrap(), position.next().unwrap());
+            assert!(
+                snippet.contains(&format!("--> x.ftl:{}:{}\n", line, column)),
+                "{}\n{}",
+                title,
+                snippet
+            );
+        }
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(
+            entries("# comment\nhello = Hi\n    .attr = x\n-brand = Baked\n"),
+            vec![("hello".to_string(), 10), ("-brand".to_string(), 35)]
+        );
+    }
+
+    #[test]
+    fn merge_conflicts() {
+        let overridden = [locale(&[
+            (0, "", "a = A\n-t = T"),
+            (1, "", "a = B\n-t = U"),
+        ])];
+        assert!(check_definitions(&overridden, OnConflict::Override).is_ok());
+        let errors = check_definitions(&overridden, OnConflict::Error).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("`a` is defined in both `0.ftl` and `1.ftl`"));
+        assert!(errors[1].contains("`-t` is defined in both `0.ftl` and `1.ftl`"));
+
+        // namespaces keep messages apart
+        let separate = [locale(&[
+            (0, "", "a = A\n-t = T"),
+            (1, "auth", "a = B\n-t = U"),
+        ])];
+        assert!(check_definitions(&separate, OnConflict::Error).is_ok());
+
+        let clash = [locale(&[(0, "", "auth = A"), (1, "auth", "login = L")])];
+        let err = only_error(check_definitions(&clash, OnConflict::Override));
+        assert!(err.contains("same name as the `auth` namespace"));
+    }
+
+    #[test]
+    fn every_duplicate() {
+        let locales = [
+            locale(&[(0, "", "a = A\nb = B\na = C"), (1, "", "b = D")]),
+            locale(&[(0, "", "-t = T\n-t = U")]),
+        ];
+        let errors = check_definitions(&locales, OnConflict::Error).unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].contains("`a` is defined more than once"));
+        assert!(errors[1].contains("`b` is defined in both"));
+        assert!(errors[2].contains("`-t` is defined more than once"));
+    }
+}